@@ -0,0 +1,71 @@
+use reqwest::Response;
+use std::{sync::Mutex, time::Duration};
+use tokio::time::{sleep_until, Instant};
+
+/// Bytes-per-second budget shared by every body read of a `Retriever`.
+///
+/// Each chunk is paid for as it arrives, delaying the next read of whoever
+/// received it until the shared budget covers it. Concurrent downloads
+/// therefore split the rate between them and never burst above it for
+/// longer than a chunk.
+#[derive(Debug)]
+pub struct Bandwidth {
+    rate: u64,
+    next: Mutex<Instant>,
+}
+
+impl Bandwidth {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            rate: bytes_per_sec.max(1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn rate(&self) -> u64 { self.rate }
+
+    /// Accounts for `bytes` just received, waiting until they fit the budget.
+    pub async fn take(&self, bytes: usize) {
+        let at = {
+            let mut next = self.next.lock().unwrap();
+            let start = (*next).max(Instant::now());
+            *next = start + Duration::from_secs_f64(bytes as f64 / self.rate as f64);
+            *next
+        };
+        sleep_until(at).await;
+    }
+
+    /// Reads the whole body of `res` within the budget.
+    pub async fn read(&self, mut res: Response) -> reqwest::Result<Vec<u8>> {
+        let mut body = Vec::with_capacity(res.content_length().unwrap_or(0) as usize);
+        while let Some(chunk) = res.chunk().await? {
+            body.extend_from_slice(&chunk);
+            self.take(chunk.len()).await;
+        }
+        Ok(body)
+    }
+}
+
+/// Parses a rate like `500K` or `2M` (powers of 1024) into bytes per second.
+pub fn parse_rate(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (num, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(i) => s.split_at(i),
+        None => (s, ""),
+    };
+    let mult = match unit.to_ascii_lowercase().trim_end_matches("/s") {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        other => return Err(format!("unknown unit {:?}", other)),
+    };
+    let num = num
+        .trim()
+        .parse::<f64>()
+        .map_err(|e| format!("{:?}: {}", s, e))?;
+    match num * mult as f64 {
+        n if n >= 1.0 => Ok(n as u64),
+        _ => Err(format!("{:?} is less than a byte per second", s)),
+    }
+}
@@ -1,19 +1,36 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use epub_builder::{EpubBuilder, EpubContent, ZipLibrary};
-use futures::future::join_all;
-use log::{debug, info, trace};
+use futures::{future::Either, pin_mut, StreamExt};
+use log::{debug, info, warn};
 use reqwest::Url;
 use retriever::{
-    extractor::Extractor,
-    page::{Page, SepStr},
+    bandwidth::parse_rate,
+    client::{config_dir, default_jar_file, parse_header, ClientConfig},
+    dedup::{Dedup, DedupMode},
+    doctor::{diagnose, Outcome},
+    download::{DownloadEvent, Options},
+    extractor::{Extractor, Field},
+    hooks::Hooks,
+    page::{ContentType, Page, SepStr},
     presets::{realm_images, realm_index, realm_next},
     retriever::Retriever,
+    stats::table,
+    watch::{WatchConfig, WatchEvent},
+};
+use std::{
+    fmt::Debug,
+    fs,
+    fs::OpenOptions,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    time::Duration,
 };
-use std::{ffi::OsString, fmt::Debug, fs, fs::OpenOptions, io, path::PathBuf, time::Duration};
 
 #[derive(Debug, Parser)]
-#[clap(author, version, about)]
+#[clap(author, version, about, subcommand_negates_reqs = true)]
 pub struct Opt {
+    #[clap(subcommand)]
+    command: Option<Command>,
     #[clap(
         short,
         long = "manga",
@@ -42,15 +59,113 @@ pub struct Opt {
     #[clap(short, long, value_parser, display_order(5))]
     /// String contained in the next page button
     next: Option<String>,
-    #[clap(num_args(1))]
+    #[clap(num_args(1), required = true)]
     /// url to manga or novels
-    url: Url,
+    url: Option<Url>,
     #[clap(long, group = "text", display_order(6))]
     /// Use RealmScans specific extractors
     realm: bool,
     #[clap(short, long, value_parser, conflicts_with = "image", display_order(7))]
     /// Generate epub
     epub: bool,
+    #[clap(long, value_parser, display_order(8))]
+    /// Proxy for all requests (http://, https:// or socks5://)
+    proxy: Option<String>,
+    #[clap(short = 'H', long, value_parser = parse_header, display_order(9))]
+    /// Extra request header, as "Name: value"
+    header: Vec<(String, String)>,
+    #[clap(short = 'A', long, value_parser, display_order(10))]
+    /// User agent override
+    user_agent: Option<String>,
+    #[clap(short, long, value_parser, display_order(11))]
+    /// Netscape cookies.txt to import
+    cookies: Option<PathBuf>,
+    #[clap(long, value_parser, display_order(12))]
+    /// Cookie jar kept between runs [default: <config dir>/cookies.json]
+    jar: Option<PathBuf>,
+    #[clap(long, conflicts_with = "jar", display_order(13))]
+    /// Don't load or save the persistent cookie jar
+    no_jar: bool,
+    #[clap(long, display_order(14))]
+    /// Obey robots.txt, including its Crawl-delay
+    robots: bool,
+    #[clap(long, value_parser, display_order(15))]
+    /// Directory of site definitions [default: <config dir>/sites]
+    sites: Option<PathBuf>,
+    #[clap(long, display_order(16))]
+    /// Print the crawl statistics as json instead of a table
+    json: bool,
+    #[clap(long, value_parser = parse_rate, display_order(17))]
+    /// Bandwidth cap for all downloads together, e.g. 500K or 2M per second
+    limit_rate: Option<u64>,
+    #[clap(long, value_parser, display_order(18))]
+    /// Stop after this many chapters when following next links
+    max_chapters: Option<usize>,
+    #[clap(long, value_parser, display_order(19))]
+    /// Query parameter to drop from urls, `name*` drops a whole prefix
+    drop_param: Vec<String>,
+    #[clap(long, value_parser, display_order(20))]
+    /// Commands to run on download events [default: <config dir>/hooks.toml]
+    hooks: Option<PathBuf>,
+    #[clap(long, value_parser, display_order(21))]
+    /// Images saved before for the series: skip them or hardlink them
+    dedup: Option<DedupMode>,
+    #[clap(long, value_parser, display_order(22))]
+    /// Drop images found in more than this many chapters
+    drop_repeated: Option<usize>,
+}
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Find series by name on a site
+    Search {
+        #[clap(short, long)]
+        /// Name of the site definition to search
+        site: String,
+        #[clap(long)]
+        /// Print the results as json
+        json: bool,
+        #[clap(required = true)]
+        query: Vec<String>,
+    },
+    /// Run every extractor on a page and report what each field found
+    Doctor {
+        /// Url of the page, or a saved html file
+        source: String,
+        #[clap(short, long)]
+        /// Site definition to use instead of the one matching the url
+        site: Option<String>,
+        #[clap(short, long)]
+        /// Url a saved html file was downloaded from
+        base: Option<Url>,
+    },
+    /// Propose selectors for a field from an example value on a page
+    Infer {
+        /// Url of the page, or a saved html file
+        source: String,
+        /// Field the selector is for: title, index, next, next_page, links, text or images
+        field: Field,
+        /// One value the selector should produce, e.g. a chapter link
+        example: String,
+        #[clap(short, long)]
+        /// Url a saved html file was downloaded from
+        base: Option<Url>,
+        #[clap(short, long, default_value = "5")]
+        /// Number of candidates to show
+        limit: usize,
+    },
+    /// Download the chapters added since a series was last downloaded
+    Update {
+        /// Directory of the earlier download, holding its manifest.json
+        dir: PathBuf,
+        #[clap(long)]
+        /// List the pages of saved chapters again and download those that changed
+        recheck: bool,
+    },
+    /// Keep followed series up to date, checking each on its interval
+    Watch {
+        /// Config listing the series [default: <config dir>/watch.toml]
+        config: Option<PathBuf>,
+    },
 }
 
 #[tokio::main]
@@ -59,15 +174,75 @@ async fn main() -> io::Result<()> {
     env_logger::init();
 
     let args = Opt::parse();
-    let mut extractor = Extractor::default();
-    let ret = Retriever::default();
-    if args.realm {
-        extractor.set_next(Some(realm_next));
-        extractor.set_index(Some(realm_index));
-        extractor.set_images(Some(realm_images));
+    let mut config = ClientConfig::default();
+    if let Some(proxy) = &args.proxy {
+        config.proxy(proxy);
+    }
+    for (name, value) in &args.header {
+        config.header(name, value);
+    }
+    if let Some(agent) = &args.user_agent {
+        config.user_agent(agent);
+    }
+    if let Some(cookies) = &args.cookies {
+        config.cookies(cookies);
+    }
+    if !args.no_jar {
+        if let Some(jar) = args.jar.clone().or_else(default_jar_file) {
+            config.persist(jar);
+        }
+    }
+    let mut ret = Retriever::with_config(&config)?;
+    ret.respect_robots(args.robots);
+    ret.limit_bandwidth(args.limit_rate);
+    ret.drop_params(&args.drop_param);
+    if let Some(sites) = args.sites.clone().or_else(|| config_dir().map(|d| d.join("sites"))) {
+        if sites.is_dir() {
+            info!("Loaded {} site definitions", ret.load_manifests(&sites)?);
+        }
+    }
+    let hooks = args.hooks.clone().or_else(|| {
+        let default = config_dir()?.join("hooks.toml");
+        default.is_file().then_some(default)
+    });
+    if let Some(hooks) = hooks {
+        let hooks = Hooks::load(&hooks)?;
+        info!("Loaded {} hooks", hooks.hooks.len());
+        ret.set_hooks(hooks);
     }
-    if let Some(dir) = &args.output_dir {
-        std::fs::create_dir_all(dir).expect("Failed to create path to output directory.");
+    let extractor = args.realm.then(|| {
+        let mut realm = Extractor::default();
+        realm.set_next(Some(realm_next));
+        realm.set_index(Some(realm_index));
+        realm.set_images(Some(realm_images));
+        realm
+    });
+    match &args.command {
+        Some(Command::Search { site, json, query }) => {
+            return search(&ret, site, &query.join(" "), *json).await;
+        }
+        Some(Command::Doctor { source, site, base }) => {
+            let extractor = extractor.as_ref();
+            return doctor(&ret, source, site.as_deref(), base.as_ref(), extractor).await;
+        }
+        Some(Command::Infer {
+            source,
+            field,
+            example,
+            base,
+            limit,
+        }) => {
+            return infer(&ret, source, *field, example, base.as_ref(), *limit).await;
+        }
+        Some(Command::Watch { config }) => {
+            let config = config.clone().or_else(|| config_dir().map(|d| d.join("watch.toml")));
+            let config = config.ok_or_else(|| {
+                let msg = "no config directory, give the watch config's path";
+                io::Error::new(io::ErrorKind::InvalidInput, msg)
+            })?;
+            return watch(&ret, &config).await;
+        }
+        Some(Command::Update { .. }) | None => (),
     }
     let sep = if let Some(next) = args.next {
         info!("Next chapter button string: '{}'", &next);
@@ -78,60 +253,274 @@ async fn main() -> io::Result<()> {
     };
     info!("Delay: {}", &args.delay);
     info!("Looking for {}", if args.image { "images" } else { "text" });
-    let mut page: Page = args.url.try_into().unwrap();
-    page.set_next(sep);
-    let mut all_imgs = vec![];
-    if args.novel {
-        debug!("current at : {:?}", page.url);
-        let mut next = Some(page);
-        while let Ok(u) = ret
-            .fetch_next(&mut next.unwrap_or_default(), args.image)
-            .await
-        {
-            tokio::time::sleep(Duration::from_millis(args.delay)).await;
-            debug!("current at : {:?}", u.url);
-            ret.fetch_content(u, args.image).await;
-            next = u.next();
-            all_imgs.push(u.clone());
-        }
-    } else if let Ok(index) = ret.fetch_index(&mut page, args.image).await {
-        if let Ok(links) = ret.fetch_links(index, args.image).await {
-            debug!("Fetched {:?} chapters", links.content.data);
-            tokio::time::sleep(Duration::from_millis(args.delay)).await;
-            if args.image {
-                if let Some(chapters) = ret.fetch_content(links, args.image).await {
-                    for mut chapter in chapters {
-                        if let Some(images) = ret.fetch_content(&mut chapter, args.image).await {
-                            debug!("Gathered {} images", images.len());
-                            all_imgs.extend(images);
-                        }
-                    }
+    let save_to = match &args.command {
+        Some(Command::Update { dir, .. }) => dir.clone(),
+        _ => args.output_dir.clone().unwrap_or_else(|| PathBuf::from("./")),
+    };
+    let mut options = Options::default();
+    options
+        .images(args.image)
+        .output_dir(&save_to)
+        .delay(Duration::from_millis(args.delay))
+        .next_by(sep)
+        .max_chapters(args.max_chapters)
+        .dedup(Dedup {
+            mode: args.dedup.unwrap_or_default(),
+            drop_after: args.drop_repeated,
+        });
+    if let Some(extractor) = extractor {
+        options.extractor(extractor);
+    }
+    let cancel = ret.cancellation().clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            warn!("Interrupted, finishing downloads in flight");
+            cancel.cancel();
+        }
+    });
+    let mut tree = None;
+    let events = match &args.command {
+        Some(Command::Update { recheck, .. }) => {
+            options.recheck(*recheck);
+            Either::Left(ret.update_series(options).await?)
+        }
+        _ => {
+            let url = args.url.clone().expect("url is required");
+            Either::Right(ret.download_series(url, options))
+        }
+    };
+    pin_mut!(events);
+    while let Some(event) = events.next().await {
+        match event {
+            DownloadEvent::Started(url) => info!("Downloading {}", url),
+            DownloadEvent::Series(series) => info!("Series: {:?}", series.title),
+            DownloadEvent::Chapters(chapters) => debug!("Fetched {} chapters", chapters.len()),
+            DownloadEvent::Diff {
+                added,
+                changed,
+                removed,
+            } => {
+                println!("Added {} chapters", added.len());
+                for url in &added {
+                    println!("  + {}", url);
+                }
+                for url in &changed {
+                    println!("  ~ {}", url);
+                }
+                for url in &removed {
+                    println!("  - {}", url);
+                }
+            }
+            DownloadEvent::Tree(t) => tree = Some(t),
+            DownloadEvent::Queued { total, skipped } => {
+                info!("Total {} pages", total);
+                if skipped > 0 {
+                    info!("Resuming, {} pages already saved", skipped);
+                }
+            }
+            DownloadEvent::Saved { url, done, total } => debug!("[{}/{}] {}", done, total, url),
+            DownloadEvent::Duplicate { url, of: Some(of) } => debug!("{} is {:?}", url, of),
+            DownloadEvent::Duplicate { url, of: None } => debug!("Dropped repeated {}", url),
+            DownloadEvent::ChapterSaved { url, dir } => info!("Saved {} into {:?}", url, dir),
+            DownloadEvent::Failed { url: Some(url), error } => warn!("{}: {}", url, error),
+            DownloadEvent::Failed { url: None, error } => warn!("{}", error),
+            DownloadEvent::Interrupted { pending } => {
+                eprintln!("Interrupted with {} pages left, run again to resume", pending)
+            }
+            DownloadEvent::Finished { saved, failed } => {
+                info!("Saved {} pages, {} failed", saved, failed)
+            }
+        }
+    }
+    let stats = ret.stats();
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+    } else {
+        eprint!("{}", table(&stats));
+    }
+    if let Err(e) = ret.save_cookies() {
+        warn!("Failed to save cookies: {}", e);
+    }
+
+    if let (true, false, Some(tree)) = (args.epub, ret.is_cancelled(), &tree) {
+        gen_epub_for(save_to, tree);
+    }
+    Ok(())
+}
+
+async fn watch(ret: &Retriever, path: &Path) -> io::Result<()> {
+    let config = WatchConfig::load(path)?;
+    info!("Watching {} series into {:?}", config.series.len(), config.library);
+    let cancel = ret.cancellation().clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            warn!("Interrupted, stopping after the requests in flight");
+            cancel.cancel();
+        }
+    });
+    let events = ret.watch(config);
+    pin_mut!(events);
+    while let Some(event) = events.next().await {
+        match event {
+            WatchEvent::Waiting { until } => debug!("Next check at {}", until),
+            WatchEvent::Checking { url, dir } => info!("Checking {} into {:?}", url, dir),
+            WatchEvent::Download { url, event } => match event {
+                DownloadEvent::Saved { url, done, total } => debug!("[{}/{}] {}", done, total, url),
+                DownloadEvent::Failed { url: Some(page), error } => {
+                    warn!("{}: {}: {}", url, page, error)
                 }
-            } else if let Some(chapters) = ret.fetch_content(links, args.image).await {
-                trace!("Gathered {} chapters", chapters.len());
-                all_imgs.extend(chapters);
+                DownloadEvent::Failed { url: None, error } => warn!("{}: {}", url, error),
+                _ => (),
+            },
+            WatchEvent::Checked {
+                url,
+                added,
+                failed,
+                next,
+            } => {
+                if !added.is_empty() {
+                    println!("{}: added {} chapters", url, added.len());
+                }
+                let how = if failed { "with failures" } else { "fine" };
+                info!("Checked {} {}, next at {}", url, how, next);
             }
+            WatchEvent::Failed(e) => warn!("{}", e),
+        }
+    }
+    if let Err(e) = ret.save_cookies() {
+        warn!("Failed to save cookies: {}", e);
+    }
+    Ok(())
+}
+
+async fn search(ret: &Retriever, site: &str, query: &str, json: bool) -> io::Result<()> {
+    let results = ret.search(site, query).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    } else {
+        for r in &results {
+            println!("{}\t{}\t{}", r.title, r.url, r.cover.as_deref().unwrap_or("-"));
         }
     }
-    let save_to = args.output_dir.unwrap_or_else(|| PathBuf::from("./"));
-    join_all(all_imgs.chunks_mut(5).map(|a| async {
-        for p in a {
-            tokio::time::sleep(Duration::from_millis(args.delay + 100)).await;
-            ret.fetch(p, &extractor, args.image).await;
-            p.save(save_to.as_path()).await.unwrap();
+    Ok(())
+}
+
+/// Fetches `source` if it is a url, otherwise reads it as a saved page.
+async fn load_page(ret: &Retriever, source: &str, base: Option<&Url>) -> io::Result<Page> {
+    Ok(match source.parse::<Url>() {
+        Ok(url) if url.scheme().starts_with("http") => {
+            let mut page = Page::from(url);
+            page.html = Some(ret.fetch_html(&mut page).await?);
+            page
+        }
+        _ => {
+            let url = match base {
+                Some(url) => url.clone(),
+                None => Url::from_file_path(fs::canonicalize(source)?)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, source))?,
+            };
+            let mut page = Page::from(url);
+            page.html = Some(fs::read_to_string(source)?);
+            page
+        }
+    })
+}
+
+async fn doctor(
+    ret: &Retriever, source: &str, site: Option<&str>, base: Option<&Url>,
+    custom: Option<&Extractor>,
+) -> io::Result<()> {
+    let page = load_page(ret, source, base).await?;
+    let extractor = match (custom, site) {
+        (Some(e), _) => e.clone(),
+        (None, Some(name)) => ret
+            .site(name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, name))?
+            .extractor()?,
+        (None, None) => ret.extractor_for(&page.url).clone(),
+    };
+    // Panics are part of the report, keep them off the terminal
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let reports = diagnose(&extractor, &page).await;
+    std::panic::set_hook(hook);
+
+    let color = io::stdout().is_terminal();
+    let paint = |code: &str, text: String| {
+        if color {
+            format!("\x1b[{}m{:<8}\x1b[0m", code, text)
+        } else {
+            format!("{:<8}", text)
         }
-    }))
-    .await;
-    info!("Total {} pages", all_imgs.len());
+    };
+    let short = |s: &str| {
+        let s = s.split_whitespace().collect::<Vec<_>>().join(" ");
+        if s.chars().count() > 60 {
+            s.chars().take(57).collect::<String>() + "..."
+        } else {
+            s
+        }
+    };
+    println!("{}", page.url);
+    println!("{:<8} {:<8} {:>6} {:>10}  SAMPLE", "FIELD", "STATUS", "COUNT", "TIME");
+    for r in &reports {
+        let (status, sample) = match &r.outcome {
+            Outcome::Values(v) => (
+                paint("32", "ok".to_owned()),
+                v.iter().take(3).map(|s| short(s)).collect::<Vec<_>>().join(" | "),
+            ),
+            Outcome::BadUrls(_, bad) => (
+                paint("31", "BAD URL".to_owned()),
+                format!("{} unparsable, e.g. {}", bad.len(), short(&bad[0])),
+            ),
+            Outcome::Empty => (paint("33", "EMPTY".to_owned()), String::new()),
+            Outcome::Panicked(msg) => (paint("31", "PANIC".to_owned()), short(msg)),
+        };
+        println!(
+            "{:<8} {} {:>6} {:>10.2?}  {}",
+            r.field,
+            status,
+            r.count(),
+            r.elapsed,
+            sample
+        );
+    }
+    Ok(())
+}
 
-    if args.epub {
-        gen_epub_for(save_to);
+async fn infer(
+    ret: &Retriever, source: &str, field: Field, example: &str, base: Option<&Url>,
+    limit: usize,
+) -> io::Result<()> {
+    let page = load_page(ret, source, base).await?;
+    let html = page.html.as_deref().unwrap_or_default();
+    let candidates = retriever::infer::infer(html, &page.url, example, field);
+    let Some(best) = candidates.first() else {
+        eprintln!("No element on {} carries {:?}", page.url, example);
+        return Ok(());
+    };
+    for c in candidates.iter().take(limit) {
+        let (ids, classes, tags) = c.specificity;
+        println!(
+            "{:>5} matches  ({},{},{})  {}{}",
+            c.matches(),
+            ids,
+            classes,
+            tags,
+            c.pick.select,
+            c.pick.attr.as_ref().map(|a| format!("  @{}", a)).unwrap_or_default()
+        );
+        for value in c.values.iter().take(3) {
+            println!("{:>20}{}", "", value);
+        }
     }
+    println!();
+    print!("{}", best.snippet(field));
     Ok(())
 }
 
-pub fn gen_epub_for(pb: PathBuf) {
-    if let Ok(dir) = fs::read_dir(&pb) {
+pub fn gen_epub_for(pb: PathBuf, tree: &ContentType) {
+    if pb.is_dir() {
         let mut file = OpenOptions::new()
             .create(true)
             .write(true)
@@ -145,32 +534,81 @@ pub fn gen_epub_for(pb: PathBuf) {
             .metadata("lang", "en-GB")
             .unwrap()
             .inline_toc();
+        // Images run together on a page until a text leaf comes
         let mut img_tags = vec![];
-        dir.flatten().for_each(|f| {
-            if f.path().extension().unwrap() != "epub" {
-                let image = OpenOptions::new().read(true).open(f.path()).unwrap();
-                let mut img_path = OsString::from(""); //
-                img_path.push(f.path().file_name().unwrap());
-                debug!("{:?}", &img_path);
-                book.add_resource(
-                    &img_path,
-                    image,
-                    format!("image/{}", f.path().extension().unwrap().to_str().unwrap()),
-                )
-                .unwrap();
-                img_tags.push(format!(
-                    r##"<img src="{0}" alt="{0}" />"##,
-                    img_path.to_str().unwrap()
-                ));
+        let mut pages = 0;
+        let mut flush = |book: &mut EpubBuilder<ZipLibrary>, tags: &mut Vec<String>| {
+            if tags.is_empty() {
+                return;
             }
-        });
-        book.add_content(EpubContent::new(
-            "Content.xhtml",
-            format!(
-                r##"
+            let name = match pages {
+                0 => "Content.xhtml".to_owned(),
+                n => format!("Content-{}.xhtml", n + 1),
+            };
+            pages += 1;
+            let page = xhtml("Book", &tags.join("\n"));
+            book.add_content(EpubContent::new(name, page.as_bytes())).unwrap();
+            tags.clear();
+        };
+        for (path, _) in tree.leaves() {
+            let Ok(leaf) = OpenOptions::new().read(true).open(pb.join(&path)) else {
+                continue;
+            };
+            let leaf_path = path.to_string_lossy().into_owned();
+            debug!("{:?}", leaf_path);
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+            if ext == "txt" {
+                flush(&mut book, &mut img_tags);
+                let Ok(text) = io::read_to_string(leaf) else {
+                    continue;
+                };
+                let title = path.parent().and_then(Path::to_str).unwrap_or_default();
+                let paragraphs = text
+                    .lines()
+                    .filter(|l| !l.trim().is_empty())
+                    .map(|l| format!("<p>{}</p>", escape(l)))
+                    .collect::<Vec<_>>();
+                let page = xhtml(&escape(title), &paragraphs.join("\n"));
+                let name = path.with_extension("xhtml").to_string_lossy().into_owned();
+                book.add_content(EpubContent::new(name, page.as_bytes()).title(title))
+                    .unwrap();
+                continue;
+            }
+            let Some(mime) = image_type(ext) else {
+                debug!("{} is no image, leaving it out", leaf_path);
+                continue;
+            };
+            book.add_resource(&leaf_path, leaf, mime).unwrap();
+            img_tags.push(format!(r##"<img src="{0}" alt="{0}" />"##, escape(&leaf_path)));
+        }
+        flush(&mut book, &mut img_tags);
+        book.generate(&mut file).unwrap();
+    }
+}
+
+/// Media type of an image file with extension `ext`, if the epub can hold it.
+fn image_type(ext: &str) -> Option<&'static str> {
+    match ext.to_ascii_lowercase().as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "svg" => Some("image/svg+xml"),
+        _ => None,
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// XHTML content document titled `title` holding the markup `body`.
+fn xhtml(title: &str, body: &str) -> String {
+    format!(
+        r##"<?xml version="1.0" encoding="UTF-8"?>
 <html xmlns="http://www.w3.org/1999/xhtml" xml:lang="en" lang="en">
-  <title>Book</title>
   <head>
+    <title>{}</title>
     <meta name="viewport" content="width=device-width, initial-scale=1" />
   </head>
   <body>
@@ -179,14 +617,6 @@ pub fn gen_epub_for(pb: PathBuf) {
     </div>
   </body>
 </html>"##,
-                {
-                    img_tags.sort_by(|a, b| a.partial_cmp(b).unwrap());
-                    img_tags.join("\n")
-                }
-            )
-            .as_bytes(),
-        ))
-        .unwrap();
-        book.generate(&mut file).unwrap();
-    }
+        title, body
+    )
 }
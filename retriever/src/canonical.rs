@@ -0,0 +1,80 @@
+use reqwest::Url;
+use select::{document::Document, predicate::Name};
+use url::form_urlencoded;
+
+/// Query parameters dropped by default, a trailing `*` matches any suffix.
+pub const DEFAULT_DENYLIST: &[&str] = &[
+    "utm_*", "fbclid", "gclid", "dclid", "msclkid", "mc_cid", "mc_eid", "_ga",
+];
+
+fn denied(name: &str, deny: &[String]) -> bool {
+    let matches = |pat: &str| match pat.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pat,
+    };
+    DEFAULT_DENYLIST.iter().any(|p| matches(p)) || deny.iter().any(|p| matches(p))
+}
+
+/// `url` without its fragment and the query parameters of
+/// `DEFAULT_DENYLIST`. Parsing already lowercased the host and dropped a
+/// default port.
+pub fn normalize(url: Url) -> Url { normalize_with(url, &[]) }
+
+/// `normalize`, also dropping the query parameters matching `deny`. The
+/// query is left as given unless a parameter is dropped from it.
+pub fn normalize_with(mut url: Url, deny: &[String]) -> Url {
+    if !matches!(url.scheme(), "http" | "https") {
+        return url;
+    }
+    url.set_fragment(None);
+    if let Some(query) = url.query() {
+        let params = query.split('&').collect::<Vec<_>>();
+        let kept = params
+            .iter()
+            .filter(|p| {
+                let name = form_urlencoded::parse(p.as_bytes()).next();
+                !name.is_some_and(|(k, _)| denied(&k, deny))
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        if kept.len() < params.len() {
+            let kept = kept.join("&");
+            url.set_query((!kept.is_empty()).then_some(kept.as_str()));
+        }
+    }
+    url
+}
+
+/// Normalized form of a url string, the string itself if it doesn't parse.
+pub fn normalize_str(url: &str, deny: &[String]) -> String {
+    url.parse()
+        .map(|u| normalize_with(u, deny).into())
+        .unwrap_or_else(|_| url.to_owned())
+}
+
+/// What pages are told apart by: the url without a trailing slash, as
+/// `/ch/1/` and `/ch/1` lead to the same chapter on about every site.
+pub fn page_key(url: &str) -> String {
+    let Ok(mut key) = url.parse::<Url>() else {
+        return url.to_owned();
+    };
+    if key.path().len() > 1 && key.path().ends_with('/') {
+        let path = key.path().trim_end_matches('/').to_owned();
+        key.set_path(&path);
+    }
+    key.into()
+}
+
+/// The `<link rel="canonical">` of a document, resolved against `base`.
+pub fn canonical_link(doc: &Document, base: &Url) -> Option<Url> {
+    doc.find(Name("link"))
+        .filter(|n| {
+            n.attr("rel").is_some_and(|r| {
+                r.split_whitespace()
+                    .any(|t| t.eq_ignore_ascii_case("canonical"))
+            })
+        })
+        .find_map(|n| base.join(n.attr("href")?.trim()).ok())
+        .filter(|u| matches!(u.scheme(), "http" | "https"))
+        .map(normalize)
+}
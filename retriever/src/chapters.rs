@@ -0,0 +1,140 @@
+use crate::{canonical::page_key, page::Page, retriever::Retriever};
+use futures::{stream, Stream};
+#[allow(unused_imports)]
+use log::{debug, info, trace, warn};
+use reqwest::Url;
+use std::{collections::HashSet, time::Duration};
+use tokio::time::sleep;
+
+/// Most pages followed within a single chapter.
+const MAX_PAGES: usize = 1000;
+
+/// Following state of `Retriever::chapters`.
+struct Crawl {
+    next: Option<Page>,
+    /// Keys of the chapters yielded so far
+    visited: HashSet<String>,
+    count: usize,
+}
+
+impl Retriever {
+    /// Follows the next links starting at `start`, yielding each chapter
+    /// once it's been fetched.
+    ///
+    /// The crawl ends after `max` chapters, when a chapter has no next link,
+    /// or when the link is a placeholder, points back to the chapter itself,
+    /// to the series index or to any chapter already yielded. `delay` is
+    /// waited before every request but the first.
+    pub fn chapters(
+        &self, start: Page, visual: bool, max: Option<usize>, delay: Duration,
+    ) -> impl Stream<Item = Page> + '_ {
+        let crawl = Crawl {
+            next: Some(start),
+            visited: HashSet::new(),
+            count: 0,
+        };
+        stream::unfold(crawl, move |mut crawl| async move {
+            let mut page = crawl.next.take()?;
+            if max.is_some_and(|max| crawl.count >= max) || self.is_cancelled() {
+                return None;
+            }
+            if crawl.count > 0 {
+                sleep(delay).await;
+            }
+            if let Err(e) = self.check_page(&mut page, visual).await {
+                warn!("{}", e);
+                return None;
+            }
+            crawl.visited.insert(page_key(page.url.as_str()));
+            crawl.visited.insert(page.key());
+            crawl.count += 1;
+            crawl.next = self.follow(&page, &crawl.visited);
+            Some((page, crawl))
+        })
+    }
+
+    /// Contents of a chapter that may be split over several pages, e.g. one
+    /// image each. `chapter` is fetched, then its next page links are
+    /// followed for as long as they stay within the chapter, and what every
+    /// page holds is returned in reading order. `delay` is waited before
+    /// each page after the first.
+    pub async fn chapter_pages(
+        &self, chapter: &mut Page, visual: bool, delay: Duration,
+    ) -> Option<Vec<Page>> {
+        let mut out = self.fetch_content(chapter, visual).await?;
+        let mut seen = out.iter().map(|p| p.url.clone()).collect::<HashSet<_>>();
+        let mut visited = HashSet::from([page_key(chapter.url.as_str()), chapter.key()]);
+        // The next chapter, where the pages of this one end
+        let boundary = chapter.next_url();
+        let mut next = chapter.next_page_url();
+        while let Some(url) = next.take() {
+            if !same_chapter(&chapter.url, &url) || boundary.as_ref() == Some(&url) {
+                debug!("{} is past the end of {}", url, chapter.url);
+                break;
+            }
+            if visited.len() >= MAX_PAGES || !visited.insert(page_key(url.as_str())) {
+                warn!("Stopped following the pages of {} at {}", chapter.url, url);
+                break;
+            }
+            sleep(delay).await;
+            let mut page = Page::from(url);
+            page.next_by = chapter.next_by;
+            let Some(found) = self.fetch_content(&mut page, visual).await else {
+                break;
+            };
+            trace!("{} more on {}", found.len(), page.url);
+            out.extend(found.into_iter().filter(|p| seen.insert(p.url.clone())));
+            next = page.next_page_url();
+        }
+        debug!("{} spans {} pages", chapter.url, visited.len());
+        Some(out)
+    }
+
+    /// The page after `page`, unless the crawl should end there.
+    fn follow(&self, page: &Page, visited: &HashSet<String>) -> Option<Page> {
+        let Some(url) = page.next_url() else {
+            debug!("No next chapter after {}", page.url);
+            return None;
+        };
+        let url = self.normalize(url);
+        let key = page_key(url.as_str());
+        if key == page_key(page.url.as_str()) || key == page.key() {
+            info!("{} links to itself, stopping", page.url);
+            return None;
+        }
+        if visited.contains(&key) {
+            warn!("{} leads back to {}, stopping", page.url, url);
+            return None;
+        }
+        let index = page.content.index().as_ref().and_then(|i| page.url.join(i).ok());
+        if index.is_some_and(|i| page_key(self.normalize(i).as_str()) == key) {
+            info!("{} leads back to the index, stopping", page.url);
+            return None;
+        }
+        let mut next = Page::from(url);
+        next.next_by = page.next_by;
+        Some(next)
+    }
+}
+
+/// Whether `url` can be a further page of the chapter at `chapter`: on the
+/// same host, with the chapter's path, a path below it, or its path
+/// followed by `-` or `_` and more, extensions aside. `?page=2`,
+/// `/ch-5/2` and `/ch-5-2.html` continue `/ch-5`, `/ch-50` doesn't.
+pub fn same_chapter(chapter: &Url, url: &Url) -> bool {
+    fn stem(url: &Url) -> &str {
+        let path = url.path().trim_end_matches('/');
+        match path.rsplit_once('.') {
+            Some((stem, ext)) if !ext.contains('/') => stem,
+            _ => path,
+        }
+    }
+    if chapter.host() != url.host() || chapter.port() != url.port() {
+        return false;
+    }
+    let base = stem(chapter);
+    match stem(url).strip_prefix(base) {
+        Some(rest) => rest.is_empty() || rest.starts_with(['/', '-', '_']),
+        None => false,
+    }
+}
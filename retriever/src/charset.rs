@@ -0,0 +1,56 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+/// How far into the body `<meta charset>` is looked for.
+const PRESCAN: usize = 4096;
+
+/// Encoding of an html body: its byte order mark, then the charset of the
+/// `Content-Type` header, then a `<meta>` declaration, utf-8 otherwise.
+pub fn sniff(body: &[u8], header: Option<&str>) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(body) {
+        return encoding;
+    }
+    header
+        .and_then(|c| Encoding::for_label(c.trim().as_bytes()))
+        .or_else(|| meta_charset(body))
+        .unwrap_or(UTF_8)
+}
+
+/// Decodes an html body, `forced` taking the place of whatever the header
+/// and `<meta>` declare. A byte order mark always wins.
+pub fn decode(body: &[u8], header: Option<&str>, forced: Option<&'static Encoding>) -> String {
+    let encoding = forced.unwrap_or_else(|| sniff(body, header));
+    encoding.decode(body).0.into_owned()
+}
+
+/// Charset named by `<meta charset>` or `<meta http-equiv="Content-Type">`
+/// near the start of the body.
+pub fn meta_charset(body: &[u8]) -> Option<&'static Encoding> {
+    let head = body[..body.len().min(PRESCAN)].to_ascii_lowercase();
+    let mut rest = head.as_slice();
+    while let Some(start) = find(rest, b"<meta") {
+        let tag = &rest[start..];
+        let tag = &tag[..find(tag, b">").unwrap_or(tag.len())];
+        if let Some(at) = find(tag, b"charset=") {
+            let value = tag[at + 8..]
+                .iter()
+                .skip_while(|b| matches!(b, b'"' | b'\'' | b' '))
+                .take_while(|b| !matches!(b, b'"' | b'\'' | b' ' | b';' | b'/' | b'>'))
+                .copied()
+                .collect::<Vec<_>>();
+            if let Some(encoding) = Encoding::for_label(&value) {
+                // A body that could be read this far isn't utf-16
+                return Some(if encoding == UTF_16LE || encoding == UTF_16BE {
+                    UTF_8
+                } else {
+                    encoding
+                });
+            }
+        }
+        rest = &rest[start + 5..];
+    }
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
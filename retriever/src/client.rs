@@ -0,0 +1,290 @@
+use crate::error::Error;
+#[allow(unused_imports)]
+use log::{debug, info, trace};
+use cookie_store::CookieStore;
+use directories::ProjectDirs;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, COOKIE},
+    redirect::Policy,
+    Client,
+    Proxy,
+    StatusCode,
+    Url,
+};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+use time::OffsetDateTime;
+
+pub static DEFAULT_AGENT: &str =
+    "Mozilla/5.0 (X11; Linux x86_64; rv:109.0) Gecko/20100101 Firefox/115.0";
+
+/// Settings used to build the `reqwest::Client` behind a `Retriever`.
+#[derive(Debug, Clone, Default)]
+pub struct ClientConfig {
+    proxy: Option<String>,
+    headers: Vec<(String, String)>,
+    user_agent: Option<String>,
+    cookies: Option<PathBuf>,
+    jar: Option<PathBuf>,
+}
+
+impl ClientConfig {
+    /// http://, https:// or socks5:// proxy used for every request
+    pub fn proxy<T: Into<String>>(&mut self, url: T) -> &mut Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// Extra header sent with every request
+    pub fn header<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) -> &mut Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn user_agent<T: Into<String>>(&mut self, agent: T) -> &mut Self {
+        self.user_agent = Some(agent.into());
+        self
+    }
+
+    /// Netscape `cookies.txt` imported into the cookie store
+    pub fn cookies<T: Into<PathBuf>>(&mut self, path: T) -> &mut Self {
+        self.cookies = Some(path.into());
+        self
+    }
+
+    /// File the cookie jar is loaded from and saved to between runs
+    pub fn persist<T: Into<PathBuf>>(&mut self, path: T) -> &mut Self {
+        self.jar = Some(path.into());
+        self
+    }
+
+    pub fn get_proxy(&self) -> Option<&String> { self.proxy.as_ref() }
+
+    pub fn get_headers(&self) -> &[(String, String)] { &self.headers }
+
+    pub fn get_user_agent(&self) -> &str { self.user_agent.as_deref().unwrap_or(DEFAULT_AGENT) }
+
+    pub fn get_cookies(&self) -> Option<&PathBuf> { self.cookies.as_ref() }
+
+    pub fn get_persist(&self) -> Option<&PathBuf> { self.jar.as_ref() }
+
+    pub fn build(&self) -> Result<(Client, Arc<CookieJar>), Error> {
+        let jar = Arc::new(match &self.jar {
+            Some(path) => CookieJar::load(path)?,
+            None => CookieJar::default(),
+        });
+        if let Some(path) = &self.cookies {
+            let src = std::fs::read_to_string(path)?;
+            let cookies = parse_cookies_txt(&src)?;
+            info!("Imported {} cookies from {:?}", cookies.len(), path);
+            for (cookie, url) in cookies {
+                jar.add_cookie_str(&cookie, &url);
+            }
+        }
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let key = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| Error::Header(name.to_owned()))?;
+            // The client keeps one value per default header, so repeated
+            // ones are combined into a list the way HTTP allows
+            let value = match headers.get(&key) {
+                Some(had) => {
+                    let sep = if key == COOKIE { "; " } else { ", " };
+                    format!("{}{}{}", had.to_str().unwrap_or_default(), sep, value)
+                }
+                None => value.to_owned(),
+            };
+            let value =
+                HeaderValue::from_str(&value).map_err(|_| Error::Header(value.clone()))?;
+            headers.insert(key, value);
+        }
+        let mut builder = Client::builder()
+            .user_agent(self.get_user_agent())
+            .default_headers(headers)
+            .connection_verbose(true)
+            .cookie_provider(jar.clone())
+            .http2_adaptive_window(true)
+            .redirect(redirect_policy());
+        if let Some(proxy) = &self.proxy {
+            debug!("Using proxy: {}", proxy);
+            builder = builder.proxy(Proxy::all(proxy.as_str())?);
+        }
+        Ok((builder.build()?, jar))
+    }
+}
+
+/// Follows redirects like reqwest's default policy, but hands permanent
+/// redirects to another host back to the retriever so it can record the move.
+fn redirect_policy() -> Policy {
+    Policy::custom(|attempt| {
+        let permanent = matches!(
+            attempt.status(),
+            StatusCode::MOVED_PERMANENTLY | StatusCode::PERMANENT_REDIRECT
+        );
+        let from = attempt.previous().last().and_then(Url::host_str);
+        if permanent && from != attempt.url().host_str() {
+            attempt.stop()
+        } else if attempt.previous().len() > 10 {
+            attempt.error("too many redirects")
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+/// The user's config directory for the retriever.
+pub fn config_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "retriever").map(|d| d.config_dir().to_path_buf())
+}
+
+/// Location of the persistent cookie jar, inside the user's config directory.
+pub fn default_jar_file() -> Option<PathBuf> { config_dir().map(|d| d.join("cookies.json")) }
+
+/// Parses a `Name: value` header as given on the command line.
+pub fn parse_header(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("header must look like 'Name: value', got {:?}", s))?;
+    let name = name.trim();
+    HeaderName::from_bytes(name.as_bytes()).map_err(|e| format!("{:?}: {}", name, e))?;
+    Ok((name.to_owned(), value.trim().to_owned()))
+}
+
+/// Cookie store that, unlike `reqwest::cookie::Jar`, can be written to disk.
+#[derive(Debug, Default)]
+pub struct CookieJar(RwLock<CookieStore>);
+
+impl CookieJar {
+    /// Loads a jar saved with `save`, dropping expired cookies. A missing file
+    /// gives an empty jar.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let store = match File::open(path) {
+            Ok(file) => CookieStore::load_json(BufReader::new(file))
+                .map_err(|e| Error::Cookies(format!("{:?}: {}", path, e)))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => CookieStore::default(),
+            Err(e) => return Err(e.into()),
+        };
+        debug!("Loaded {} cookies from {:?}", store.iter_unexpired().count(), path);
+        Ok(Self(RwLock::new(store)))
+    }
+
+    /// Writes every unexpired cookie, session cookies included, so that age
+    /// gates and logins survive a restart.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        self.prune();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        for cookie in self.0.read().unwrap().iter_unexpired() {
+            let line = serde_json::to_string(cookie).map_err(|e| Error::Cookies(e.to_string()))?;
+            writeln!(file, "{}", line)?;
+        }
+        file.sync_all()?;
+        fs::rename(tmp, path)?;
+        trace!("Saved cookies to {:?}", path);
+        Ok(())
+    }
+
+    /// Removes expired cookies from the store.
+    pub fn prune(&self) {
+        let mut store = self.0.write().unwrap();
+        let expired = store
+            .iter_any()
+            .filter(|c| c.is_expired())
+            .map(|c| (String::from(&c.domain), String::from(&c.path), c.name().to_owned()))
+            .collect::<Vec<_>>();
+        for (domain, path, name) in expired {
+            store.remove(&domain, &path, &name);
+        }
+    }
+
+    pub fn has_cookie(&self, url: &Url, name: &str) -> bool {
+        self.0
+            .read()
+            .unwrap()
+            .get_request_values(url)
+            .any(|(n, _)| n == name)
+    }
+
+    pub fn add_cookie_str(&self, cookie: &str, url: &Url) {
+        if let Err(e) = self.0.write().unwrap().parse(cookie, url) {
+            debug!("Rejected cookie for {}: {}", url, e);
+        }
+    }
+}
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let mut store = self.0.write().unwrap();
+        for header in cookie_headers.filter_map(|h| h.to_str().ok()) {
+            let _ = store.parse(header, url);
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let s = self
+            .0
+            .read()
+            .unwrap()
+            .get_request_values(url)
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if s.is_empty() {
+            return None;
+        }
+        HeaderValue::from_str(&s).ok()
+    }
+}
+
+/// Parses a Netscape `cookies.txt` into `Set-Cookie` strings and the urls
+/// they belong to. Expired cookies are skipped.
+pub fn parse_cookies_txt(src: &str) -> Result<Vec<(String, Url)>, Error> {
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    let mut out = vec![];
+    for (nr, line) in src.lines().enumerate() {
+        let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+            Some(l) => (l, true),
+            None => (line, false),
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.split('\t').collect::<Vec<_>>();
+        let &[domain, subdomains, path, secure, expires, name, value] = fields.as_slice() else {
+            return Err(Error::Cookies(format!("line {}: expected 7 fields", nr + 1)));
+        };
+        let expires = expires
+            .parse::<i64>()
+            .map_err(|_| Error::Cookies(format!("line {}: bad expiry", nr + 1)))?;
+        if expires != 0 && expires <= now {
+            trace!("Skipping expired cookie: {}", name);
+            continue;
+        }
+        let host = domain.trim_start_matches('.');
+        let secure = secure.eq_ignore_ascii_case("TRUE");
+        let url = format!("{}://{}{}", if secure { "https" } else { "http" }, host, path)
+            .parse::<Url>()
+            .map_err(|_| Error::Cookies(format!("line {}: bad domain", nr + 1)))?;
+        let mut cookie = format!("{}={}; Path={}", name, value, path);
+        if subdomains.eq_ignore_ascii_case("TRUE") {
+            cookie += &format!("; Domain={}", host);
+        }
+        if expires != 0 {
+            cookie += &format!("; Max-Age={}", expires - now);
+        }
+        if secure {
+            cookie += "; Secure";
+        }
+        if http_only {
+            cookie += "; HttpOnly";
+        }
+        out.push((cookie, url));
+    }
+    Ok(out)
+}
@@ -0,0 +1,177 @@
+use crate::page::ContentType;
+#[allow(unused_imports)]
+use log::{debug, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    mem::take,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use tokio::{
+    fs::{hard_link, remove_file, write},
+    io,
+    sync::Mutex,
+};
+use uuid::Uuid;
+
+/// What happens to an image whose exact bytes were saved before.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupMode {
+    /// Saved again
+    #[default]
+    Off,
+    /// Not saved, the earlier copy stands for it
+    Skip,
+    /// Saved as a hardlink to the earlier copy
+    Hardlink,
+}
+/// Deduplication of the images of a series.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Dedup {
+    pub mode: DedupMode,
+    /// Drop images found in more than this many chapters, like credit or ad
+    /// pages, removing the copies already saved
+    pub drop_after: Option<usize>,
+}
+/// Hashes of the images saved for a series, kept in its directory.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HashIndex {
+    /// What is known of each hash, by `content_hash`
+    pub hashes: BTreeMap<String, Seen>,
+}
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Seen {
+    /// Files holding the image, relative to the series directory
+    pub files: Vec<PathBuf>,
+    /// Urls of the chapters it was found in
+    pub chapters: BTreeSet<String>,
+    /// Found in too many chapters, no longer saved
+    pub dropped: bool,
+}
+/// What `HashIndex::check` decided for an image.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Verdict {
+    Write,
+    /// Already saved in the file
+    Skip(PathBuf),
+    /// Link to the file
+    Link(PathBuf),
+    /// Not saved, the files holding it are to be removed
+    Drop(Vec<PathBuf>),
+}
+/// How `store` kept an image.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Stored {
+    Written,
+    /// Skipped or linked, the file it duplicates
+    Duplicate(PathBuf),
+    Dropped,
+}
+
+/// Name-based UUID of `data`, the hash images are told apart by.
+pub fn content_hash(data: &[u8]) -> Uuid { Uuid::new_v5(&Uuid::NAMESPACE_OID, data) }
+
+impl Dedup {
+    pub fn is_off(&self) -> bool { self.mode == DedupMode::Off && self.drop_after.is_none() }
+}
+impl FromStr for DedupMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" | "none" => Ok(DedupMode::Off),
+            "skip" => Ok(DedupMode::Skip),
+            "hardlink" | "link" => Ok(DedupMode::Hardlink),
+            other => Err(format!("unknown dedup mode {:?}, use skip or hardlink", other)),
+        }
+    }
+}
+impl HashIndex {
+    pub const FILE: &'static str = "hashes.json";
+
+    /// Writes `hashes.json` into `dir`.
+    pub async fn save(&self, dir: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        write(dir.join(Self::FILE), json).await
+    }
+
+    pub async fn load(dir: &Path) -> io::Result<Self> {
+        let json = tokio::fs::read(dir.join(Self::FILE)).await?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+
+    /// Records `data` found in `chapter` and bound for `file` under `root`,
+    /// deciding whether it is saved. Files since deleted are forgotten.
+    pub fn check(
+        &mut self, data: &[u8], root: &Path, file: &Path, chapter: &str, dedup: &Dedup,
+    ) -> Verdict {
+        let seen = self.hashes.entry(content_hash(data).to_string()).or_default();
+        seen.chapters.insert(chapter.to_owned());
+        seen.files.retain(|f| f == file || root.join(f).exists());
+        if seen.dropped {
+            return Verdict::Drop(vec![]);
+        }
+        if dedup.drop_after.is_some_and(|n| seen.chapters.len() > n) {
+            debug!("{:?} is in {} chapters, dropping it", file, seen.chapters.len());
+            seen.dropped = true;
+            return Verdict::Drop(take(&mut seen.files));
+        }
+        let earlier = seen.files.iter().find(|f| *f != file).cloned();
+        let verdict = match (dedup.mode, earlier) {
+            (DedupMode::Skip, Some(f)) => return Verdict::Skip(f),
+            (DedupMode::Hardlink, Some(f)) => Verdict::Link(f),
+            _ => Verdict::Write,
+        };
+        if !seen.files.iter().any(|f| f == file) {
+            seen.files.push(file.to_owned());
+        }
+        verdict
+    }
+
+    /// Whether `data` is found in too many chapters to be kept.
+    pub fn is_dropped(&self, data: &[u8]) -> bool {
+        let seen = self.hashes.get(&content_hash(data).to_string());
+        seen.is_some_and(|s| s.dropped)
+    }
+}
+
+/// Saves `data` as `file` under `root` unless `index` knows it as a
+/// duplicate. Hardlinks that can't be made, e.g. across file systems, are
+/// written as copies.
+pub async fn store(
+    index: &Mutex<HashIndex>, dedup: &Dedup, data: &ContentType, root: &Path, file: &Path,
+    chapter: &str,
+) -> io::Result<Stored> {
+    let path = root.join(file);
+    // Held until the file is in place, later checks find it on disk
+    let mut index = index.lock().await;
+    match index.check(&data.as_data(), root, file, chapter, dedup) {
+        Verdict::Write => data.write(&path).await.map(|_| Stored::Written),
+        Verdict::Skip(f) => Ok(Stored::Duplicate(f)),
+        Verdict::Link(f) => {
+            let _ = remove_file(&path).await;
+            match hard_link(root.join(&f), &path).await {
+                Ok(()) => Ok(Stored::Duplicate(f)),
+                Err(e) => {
+                    debug!("Can't link {:?} to {:?}, copying: {}", path, f, e);
+                    data.write(&path).await.map(|_| Stored::Written)
+                }
+            }
+        }
+        Verdict::Drop(files) => {
+            for f in files {
+                match remove_file(root.join(&f)).await {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                        warn!("Failed to remove repeated image {:?}: {}", f, e)
+                    }
+                    _ => (),
+                }
+            }
+            Ok(Stored::Dropped)
+        }
+    }
+}
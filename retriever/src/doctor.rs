@@ -0,0 +1,122 @@
+use crate::{
+    extractor::{Extractor, Field},
+    page::{ContentType, Page},
+};
+use futures::FutureExt;
+use reqwest::Url;
+use std::{
+    any::Any,
+    future::Future,
+    panic::AssertUnwindSafe,
+    time::{Duration, Instant},
+};
+
+/// What one extractor field produced for a page.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub field: &'static str,
+    pub outcome: Outcome,
+    pub elapsed: Duration,
+}
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    Values(Vec<String>),
+    /// Values of which some are not urls the pipeline can follow, it drops
+    /// those
+    BadUrls(Vec<String>, Vec<String>),
+    /// The field returned `None`
+    Empty,
+    Panicked(String),
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool { matches!(self.outcome, Outcome::Values(_)) }
+
+    pub fn count(&self) -> usize {
+        match &self.outcome {
+            Outcome::Values(v) | Outcome::BadUrls(v, _) => v.len(),
+            _ => 0,
+        }
+    }
+}
+
+/// Runs every extractor field over `page`, which must still hold its html.
+pub async fn diagnose(extractor: &Extractor, page: &Page) -> Vec<Report> {
+    fn one(v: Option<String>) -> Option<Vec<String>> { v.map(|s| vec![s]) }
+    fn list(v: Option<ContentType>) -> Option<Vec<String>> {
+        match v? {
+            ContentType::Text(v, _) | ContentType::Images(v, _) | ContentType::Chapters(v) => Some(v),
+            other => Some(vec![format!("{:?}", other)]),
+        }
+    }
+    vec![
+        run("title", Some(Field::Title), page, extractor.get_title(page).map(one)).await,
+        run("index", Some(Field::Index), page, extractor.get_index(page).map(one)).await,
+        run("next", Some(Field::Next), page, extractor.get_next(page).map(one)).await,
+        run(
+            "next_page",
+            Some(Field::NextPage),
+            page,
+            extractor.get_next_page(page).map(one),
+        )
+        .await,
+        run("links", Some(Field::Links), page, extractor.get_links(page)).await,
+        run("text", Some(Field::Text), page, extractor.get_text(page).map(list)).await,
+        run("images", Some(Field::Images), page, extractor.get_images(page).map(list)).await,
+        run(
+            "series",
+            None,
+            page,
+            extractor
+                .get_series(page)
+                .map(|s| one(s.and_then(|s| serde_json::to_string(&s).ok()))),
+        )
+        .await,
+    ]
+}
+
+/// Runs the extractor function of `kind`, checking the urls it gives.
+async fn run(
+    field: &'static str, kind: Option<Field>, page: &Page,
+    fut: impl Future<Output = Option<Vec<String>>>,
+) -> Report {
+    let start = Instant::now();
+    let res = AssertUnwindSafe(fut).catch_unwind().await;
+    let elapsed = start.elapsed();
+    let outcome = match res {
+        Err(panic) => Outcome::Panicked(panic_message(panic)),
+        Ok(None) => Outcome::Empty,
+        Ok(Some(values)) if kind.is_some_and(|k| k.is_url()) => {
+            // Single links are resolved against the page, lists must be absolute
+            let single = kind.is_some_and(|k| k.is_single());
+            let bad = values
+                .iter()
+                .filter(|v| match single {
+                    true => page.url.join(v).is_err(),
+                    false => Url::parse(v).is_err(),
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            if bad.is_empty() {
+                Outcome::Values(values)
+            } else {
+                Outcome::BadUrls(values, bad)
+            }
+        }
+        Ok(Some(values)) => Outcome::Values(values),
+    };
+    log::trace!("{} on {}: {:?}", field, page.url, outcome);
+    Report {
+        field,
+        outcome,
+        elapsed,
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_owned())
+}
@@ -0,0 +1,683 @@
+use crate::{
+    dedup::{store, Dedup, HashIndex, Stored},
+    error::Error,
+    extractor::Extractor,
+    hooks::HookEvent,
+    order::sequence,
+    page::{ContentType, Node, Page, SepStr},
+    resume::{remove_partials, Resume},
+    retriever::Retriever,
+    series::{ChapterEntry, SeriesInfo, SeriesManifest},
+};
+use dashmap::DashSet;
+use futures::{
+    channel::mpsc::{unbounded, UnboundedSender},
+    future::{join_all, ready},
+    pin_mut,
+    stream,
+    FutureExt,
+    Stream,
+    StreamExt,
+};
+#[allow(unused_imports)]
+use log::{debug, info, trace, warn};
+use reqwest::Url;
+use std::{
+    collections::HashSet,
+    iter,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+use tokio::{io, time::sleep};
+
+/// How `Retriever::download_series` crawls and where it saves.
+#[derive(Debug, Clone)]
+pub struct Options {
+    images: bool,
+    output_dir: PathBuf,
+    delay: Duration,
+    next_by: SepStr,
+    max_chapters: Option<usize>,
+    extractor: Option<Extractor>,
+    known: Option<SeriesManifest>,
+    recheck: bool,
+    dedup: Dedup,
+}
+/// Progress of a download, in the order things happen.
+#[derive(Debug)]
+pub enum DownloadEvent {
+    /// Crawling started from the url
+    Started(Url),
+    /// Metadata of the series, already written to `series.json`
+    Series(SeriesInfo),
+    /// Chapters found while crawling
+    Chapters(Vec<Url>),
+    /// How the chapters differ from the ones an update started with
+    Diff {
+        added: Vec<Url>,
+        changed: Vec<Url>,
+        removed: Vec<Url>,
+    },
+    /// Layout of the series, pages are saved at its leaves
+    Tree(ContentType),
+    /// Pages to download, `skipped` of them were saved by an earlier run
+    Queued { total: usize, skipped: usize },
+    /// A page was downloaded and written, `done` counts skipped pages too
+    Saved { url: Url, done: usize, total: usize },
+    /// The page's image was saved before as `of`, or dropped for repeating
+    /// across chapters when `None`
+    Duplicate { url: Url, of: Option<PathBuf> },
+    /// The last missing page of the chapter was saved into `dir`
+    ChapterSaved { url: Url, dir: PathBuf },
+    Failed { url: Option<Url>, error: Error },
+    /// Cancelled with `pending` pages left, progress is in `resume.json`
+    Interrupted { pending: usize },
+    /// Always the last event
+    Finished { saved: usize, failed: usize },
+}
+
+impl Options {
+    /// Download images when set, text otherwise
+    pub fn images(&mut self, images: bool) -> &mut Self {
+        self.images = images;
+        self
+    }
+
+    pub fn output_dir<T: Into<PathBuf>>(&mut self, dir: T) -> &mut Self {
+        self.output_dir = dir.into();
+        self
+    }
+
+    /// Pause before each chapter and page request
+    pub fn delay(&mut self, delay: Duration) -> &mut Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Text of the next chapter link, for text downloads
+    pub fn next_by(&mut self, next_by: SepStr) -> &mut Self {
+        self.next_by = next_by;
+        self
+    }
+
+    /// Stop following next links after this many chapters
+    pub fn max_chapters(&mut self, max: Option<usize>) -> &mut Self {
+        self.max_chapters = max;
+        self
+    }
+
+    /// Extractor used instead of the site definitions' for every page
+    pub fn extractor(&mut self, extractor: Extractor) -> &mut Self {
+        self.extractor = Some(extractor);
+        self
+    }
+
+    /// Chapters saved by an earlier run, not fetched again
+    pub fn update_from(&mut self, manifest: SeriesManifest) -> &mut Self {
+        self.known = Some(manifest);
+        self
+    }
+
+    /// List the pages of known chapters again and download those that
+    /// changed, for image downloads
+    pub fn recheck(&mut self, recheck: bool) -> &mut Self {
+        self.recheck = recheck;
+        self
+    }
+
+    /// What to do with images saved before for the series
+    pub fn dedup(&mut self, dedup: Dedup) -> &mut Self {
+        self.dedup = dedup;
+        self
+    }
+
+    pub fn get_images(&self) -> bool { self.images }
+
+    pub fn get_output_dir(&self) -> &PathBuf { &self.output_dir }
+}
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            images: false,
+            output_dir: PathBuf::from("./"),
+            delay: Duration::ZERO,
+            next_by: SepStr::default(),
+            max_chapters: None,
+            extractor: None,
+            known: None,
+            recheck: false,
+            dedup: Dedup::default(),
+        }
+    }
+}
+
+impl Retriever {
+    /// Downloads the series at `url`, reporting progress as a stream.
+    ///
+    /// Nothing happens until the stream is polled. Pages saved by an
+    /// interrupted earlier run into the same directory are skipped, and
+    /// cancelling the retriever stops the download after the requests in
+    /// flight, leaving `resume.json` behind.
+    pub fn download_series(
+        &self, url: Url, options: Options,
+    ) -> impl Stream<Item = DownloadEvent> + '_ {
+        let (tx, rx) = unbounded();
+        let work = async move {
+            self.run_download(url, options, tx).await;
+            None
+        };
+        stream::select(rx.map(Some), work.into_stream()).filter_map(ready)
+    }
+
+    /// Brings the download in the output directory up to date: reads its
+    /// `manifest.json`, lists the chapters again and downloads only the new
+    /// ones, along with known ones whose pages changed when rechecking.
+    pub async fn update_series(
+        &self, mut options: Options,
+    ) -> io::Result<impl Stream<Item = DownloadEvent> + '_> {
+        let manifest = SeriesManifest::load(&options.output_dir).await?;
+        let url = manifest
+            .url
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        options.images(manifest.images).update_from(manifest);
+        Ok(self.download_series(url, options))
+    }
+
+    async fn run_download(&self, url: Url, options: Options, tx: UnboundedSender<DownloadEvent>) {
+        let emit = |event| {
+            let _ = tx.unbounded_send(event);
+        };
+        let dir = options.output_dir.as_path();
+        if let Err(e) = tokio::fs::create_dir_all(dir).await {
+            self.hooks()
+                .fire(&HookEvent::RunFailed {
+                    series: url.to_string(),
+                    dir: dir.to_owned(),
+                    failed: 0,
+                    errors: vec![format!("{}: {}", dir.display(), e)],
+                })
+                .await;
+            emit(DownloadEvent::Failed {
+                url: None,
+                error: e.into(),
+            });
+            emit(DownloadEvent::Finished {
+                saved: 0,
+                failed: 0,
+            });
+            return;
+        }
+        let stored = Resume::load(dir).await.ok();
+        emit(DownloadEvent::Started(url.clone()));
+
+        let visual = options.images;
+        // Urls saved before the site moved hosts point to where it is now
+        let relocate = |u: &str| match u.parse() {
+            Ok(u) => self.relocate(&u).to_string(),
+            Err(_) => u.to_owned(),
+        };
+        let known = options.known.as_ref();
+        let find_known = |url: &Url| {
+            let url = url.as_str();
+            known?
+                .chapters
+                .iter()
+                .find(|c| c.url == url || relocate(&c.url) == url)
+        };
+        // Text is followed chapter by chapter, an update picks up after the
+        // last one saved
+        let from = match known.and_then(|k| k.chapters.last()) {
+            Some(last) if !visual => {
+                relocate(&last.url).parse().unwrap_or_else(|_| url.clone())
+            }
+            _ => url.clone(),
+        };
+        let mut page: Page = from.into();
+        page.set_next(options.next_by);
+        let mut pages = vec![];
+        let mut chapters = vec![];
+        let mut changed = vec![];
+        let mut series = None;
+        let mut index = None;
+        // Pages the chapters couldn't be listed from
+        let mut lost = vec![];
+        if !visual {
+            let mut first = page.clone();
+            match self.check_page(&mut first, visual).await {
+                Ok(()) => {
+                    if let Ok(index) = self.fetch_index(&mut first, visual).await {
+                        series = self.fetch_series(index, visual).await;
+                    }
+                }
+                Err(e) => lost.push((first.url.clone(), e)),
+            }
+            let mut crawled = vec![];
+            if lost.is_empty() {
+                let crawl = self.chapters(page, visual, options.max_chapters, options.delay);
+                pin_mut!(crawl);
+                while let Some(u) = crawl.next().await {
+                    debug!("current at : {:?}", u.url);
+                    if find_known(&u.url).is_none() {
+                        crawled.push(u);
+                    }
+                }
+            }
+            let saved = known.map_or(&[][..], |k| &k.chapters[..]);
+            for entry in saved {
+                let (chapter, placeholders) = known_chapter(entry, visual, relocate);
+                chapters.push((relocate(&entry.url), chapter));
+                pages.extend(placeholders);
+            }
+            let urls = saved
+                .iter()
+                .map(|c| c.url.as_str())
+                .chain(crawled.iter().map(|p| p.url.as_str()))
+                .collect::<Vec<_>>();
+            let numbers = sequence(&urls).into_iter().skip(saved.len());
+            for (u, number) in crawled.iter().zip(numbers) {
+                let mut node = Node::new(chapter_name(&number, u));
+                node.push(u.url.as_str(), ContentType::Text(vec![], None));
+                chapters.push((u.url.to_string(), ContentType::Chapter(node)));
+            }
+            pages.extend(crawled);
+        } else {
+            let start = page.url.clone();
+            let listed = match self.fetch_index(&mut page, visual).await {
+                Ok(found) => {
+                    let at = found.url.clone();
+                    self.fetch_links(found, visual).await.map_err(|e| (at, e))
+                }
+                Err(e) => Err((start, e)),
+            };
+            match listed {
+                Err(lost_at) => lost.push(lost_at),
+                Ok(links) => {
+                    index = Some(links.url.to_string());
+                    series = self.fetch_series(links, visual).await;
+                    sleep(options.delay).await;
+                    let list = self.fetch_content(links, visual).await.unwrap_or_default();
+                    let urls = list.iter().map(|p| p.url.as_str()).collect::<Vec<_>>();
+                    let numbers = sequence(&urls);
+                    for (mut chapter, number) in list.into_iter().zip(numbers) {
+                        let entry = find_known(&chapter.url);
+                        if let Some(entry) = entry.filter(|_| !options.recheck) {
+                            let (node, placeholders) = known_chapter(entry, visual, relocate);
+                            chapters.push((chapter.url.to_string(), node));
+                            pages.extend(placeholders);
+                            continue;
+                        }
+                        let images = self
+                            .chapter_pages(&mut chapter, visual, options.delay)
+                            .await
+                            .unwrap_or_default();
+                        debug!("Gathered {} images", images.len());
+                        if let Some(entry) = entry {
+                            let now = images.iter().map(|p| p.url.to_string());
+                            if now.ne(entry.pages.iter().map(|p| relocate(p))) {
+                                changed.push(chapter.url.clone());
+                            }
+                        }
+                        // A changed chapter keeps its directory
+                        let name = match entry {
+                            Some(e) => e.name.clone(),
+                            None => chapter_name(&number, &chapter),
+                        };
+                        let mut node = Node::new(name);
+                        for image in &images {
+                            node.push(image.url.as_str(), ContentType::Image(vec![]));
+                        }
+                        chapters.push((chapter.url.to_string(), ContentType::Chapter(node)));
+                        pages.extend(images);
+                    }
+                }
+            }
+        }
+        let mut errors = vec![];
+        for (at, error) in lost {
+            if matches!(error, Error::Cancelled) {
+                continue;
+            }
+            errors.push(format!("{}: {}", at, error));
+            emit(DownloadEvent::Failed {
+                url: Some(at),
+                error,
+            });
+        }
+        let lost = errors.len();
+        if known.is_some() && chapters.is_empty() {
+            // Nothing to compare with, keep what the last run saved
+            let error = Error::NoContent(url.clone());
+            errors.push(error.to_string());
+            self.hooks()
+                .fire(&HookEvent::RunFailed {
+                    series: url.to_string(),
+                    dir: dir.to_owned(),
+                    failed: lost,
+                    errors,
+                })
+                .await;
+            emit(DownloadEvent::Failed { url: None, error });
+            emit(DownloadEvent::Finished {
+                saved: 0,
+                failed: lost,
+            });
+            return;
+        }
+        let start = relocate(url.as_str());
+        let mut resume = match stored {
+            Some(mut r) => {
+                r.rewrite(relocate);
+                if r.url == start {
+                    r
+                } else {
+                    Resume::new(start)
+                }
+            }
+            None => Resume::new(start),
+        };
+        let title = series.as_ref().and_then(|s| s.title.clone());
+        if let Some(series) = series {
+            match series.save(dir).await {
+                Ok(()) => emit(DownloadEvent::Series(series)),
+                Err(e) => emit(DownloadEvent::Failed {
+                    url: None,
+                    error: e.into(),
+                }),
+            }
+        }
+        emit(DownloadEvent::Chapters(
+            chapters
+                .iter()
+                .filter_map(|(u, _)| u.parse().ok())
+                .collect(),
+        ));
+        if let Some(known) = known {
+            let listed = chapters.iter().map(|(u, _)| u.as_str()).collect::<HashSet<_>>();
+            let parse = |u: &str| u.parse::<Url>().ok();
+            emit(DownloadEvent::Diff {
+                added: chapters
+                    .iter()
+                    .filter(|(u, _)| u.parse().ok().and_then(|u| find_known(&u)).is_none())
+                    .filter_map(|(u, _)| parse(u))
+                    .collect(),
+                changed,
+                removed: known
+                    .chapters
+                    .iter()
+                    .map(|c| relocate(&c.url))
+                    .filter(|u| !listed.contains(u.as_str()))
+                    .filter_map(|u| parse(&u))
+                    .collect(),
+            });
+        }
+        // Recorded before the tree takes the chapters, kept if fully saved
+        let mut entries = chapters
+            .iter()
+            .filter_map(|(url, chapter)| {
+                let node = chapter.node()?;
+                Some(ChapterEntry {
+                    url: url.clone(),
+                    name: node.name.clone(),
+                    pages: node.sources.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let tree = ContentType::series(title.unwrap_or_default(), chapters);
+        if let Err(e) = tree.save(dir).await {
+            emit(DownloadEvent::Failed {
+                url: None,
+                error: e.into(),
+            });
+        }
+        // Leaves come in the order their pages were gathered, chapter by
+        // chapter, so each page knows the entry of its chapter
+        let owners = entries
+            .iter()
+            .enumerate()
+            .flat_map(|(i, c)| iter::repeat_n(i, c.pages.len()));
+        let mut queue = pages
+            .into_iter()
+            .zip(tree.leaves().into_iter().map(|(path, _)| dir.join(path)))
+            .zip(owners)
+            .map(|((page, path), i)| (page, path, i))
+            .collect::<Vec<_>>();
+        match remove_partials(queue.iter().map(|(_, path, _)| path.as_path())) {
+            Ok(0) => (),
+            Ok(n) => info!("Removed {} partial files", n),
+            Err(e) => warn!("Failed to clean up partial files: {}", e),
+        }
+        emit(DownloadEvent::Tree(tree));
+        // Pages of chapters an update already has stay in the tree only
+        let have = known
+            .map(|k| k.chapters.iter())
+            .into_iter()
+            .flatten()
+            .flat_map(|c| c.pages.iter().map(|p| (relocate(&c.url), relocate(p))))
+            .collect::<HashSet<_>>();
+        let had = |chapter: &str, page: &str| have.contains(&(chapter.to_owned(), page.to_owned()));
+        queue.retain(|(p, _, i)| !had(&entries[*i].url, p.url.as_str()));
+
+        let total = queue.len();
+        let skipped = queue
+            .iter()
+            .filter(|(p, ..)| resume.done.contains(p.url.as_str()))
+            .count();
+        emit(DownloadEvent::Queued { total, skipped });
+        // Pages each chapter still misses, to tell when one is complete
+        let missing = entries.iter().map(|_| AtomicUsize::new(0)).collect::<Vec<_>>();
+        for (p, _, i) in &queue {
+            if !resume.done.contains(p.url.as_str()) {
+                missing[*i].fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        let dedup = &options.dedup;
+        let hashes = match dedup.is_off() {
+            true => HashIndex::default(),
+            false => HashIndex::load(dir).await.unwrap_or_default(),
+        };
+        let hashes = tokio::sync::Mutex::new(hashes);
+        let done = DashSet::new();
+        let handled = AtomicUsize::new(0);
+        let written = AtomicUsize::new(0);
+        let failed = AtomicUsize::new(lost);
+        let errors = Mutex::new(errors);
+        join_all(queue.chunks_mut(5).map(|group| async {
+            for (p, path, i) in group {
+                if resume.done.contains(p.url.as_str()) {
+                    continue;
+                }
+                sleep(options.delay).await;
+                if self.is_cancelled() {
+                    break;
+                }
+                let extractor = options
+                    .extractor
+                    .as_ref()
+                    .unwrap_or_else(|| self.extractor_for(&p.url));
+                let chapter = &entries[*i];
+                let res = match (self.fetch(p, extractor, visual).await, &p.content.data) {
+                    (Ok(()), Some(data @ ContentType::Image(_))) if !dedup.is_off() => {
+                        let file = path.strip_prefix(dir).unwrap_or(path);
+                        store(&hashes, dedup, data, dir, file, &chapter.url)
+                            .await
+                            .map_err(Error::from)
+                    }
+                    (Ok(()), Some(data)) => data
+                        .write(path)
+                        .await
+                        .map(|_| Stored::Written)
+                        .map_err(Error::from),
+                    (Ok(()), None) => Err(Error::NoContent(p.url.clone())),
+                    (Err(e), _) => Err(e),
+                };
+                match res {
+                    Ok(stored) => {
+                        done.insert(p.url.to_string());
+                        let handled = handled.fetch_add(1, Ordering::Relaxed) + 1;
+                        let page = p.url.clone();
+                        match stored {
+                            Stored::Written => {
+                                written.fetch_add(1, Ordering::Relaxed);
+                                emit(DownloadEvent::Saved {
+                                    url: page,
+                                    done: skipped + handled,
+                                    total,
+                                });
+                            }
+                            Stored::Duplicate(of) => {
+                                let of = Some(dir.join(of));
+                                emit(DownloadEvent::Duplicate { url: page, of });
+                            }
+                            Stored::Dropped => {
+                                emit(DownloadEvent::Duplicate { url: page, of: None });
+                            }
+                        }
+                        if missing[*i].fetch_sub(1, Ordering::Relaxed) != 1 {
+                            continue;
+                        }
+                        let dir = path.parent().unwrap_or(dir).to_owned();
+                        self.hooks()
+                            .fire(&HookEvent::ChapterComplete {
+                                series: url.to_string(),
+                                chapter: chapter.url.clone(),
+                                dir: dir.clone(),
+                                pages: chapter.pages.len(),
+                            })
+                            .await;
+                        if let Ok(url) = chapter.url.parse() {
+                            emit(DownloadEvent::ChapterSaved { url, dir });
+                        }
+                    }
+                    Err(Error::Cancelled) => break,
+                    Err(error) => {
+                        failed.fetch_add(1, Ordering::Relaxed);
+                        if let Ok(mut errors) = errors.lock() {
+                            errors.push(format!("{}: {}", p.url, error));
+                        }
+                        emit(DownloadEvent::Failed {
+                            url: Some(p.url.clone()),
+                            error,
+                        });
+                    }
+                }
+            }
+        }))
+        .await;
+
+        let saved = written.into_inner();
+        if !dedup.is_off() {
+            if let Err(e) = hashes.into_inner().save(dir).await {
+                emit(DownloadEvent::Failed {
+                    url: None,
+                    error: e.into(),
+                });
+            }
+        }
+        entries.retain(|c| {
+            c.pages.iter().all(|p| {
+                done.contains(p.as_str()) || resume.done.contains(p) || had(&c.url, p)
+            })
+        });
+        let added = entries
+            .iter()
+            .filter(|c| c.url.parse().ok().and_then(|u| find_known(&u)).is_none())
+            .map(|c| c.url.clone())
+            .collect::<Vec<_>>();
+        if !entries.is_empty() {
+            let mut manifest = SeriesManifest {
+                url: relocate(url.as_str()),
+                index,
+                images: visual,
+                chapters: entries,
+                updated_at: 0,
+            };
+            if let Err(e) = manifest.save(dir).await {
+                emit(DownloadEvent::Failed {
+                    url: None,
+                    error: e.into(),
+                });
+            }
+        }
+        let res = if self.is_cancelled() {
+            resume.done.extend(done);
+            resume.pending = queue
+                .iter()
+                .map(|(p, ..)| p.url.to_string())
+                .filter(|u| !resume.done.contains(u))
+                .collect();
+            let res = resume.save(dir).await;
+            emit(DownloadEvent::Interrupted {
+                pending: resume.pending.len(),
+            });
+            res
+        } else {
+            Resume::clear(dir).await
+        };
+        if let Err(e) = res {
+            emit(DownloadEvent::Failed {
+                url: None,
+                error: e.into(),
+            });
+        }
+        let failed = failed.into_inner();
+        // A partly failed run still reports the chapters it completed
+        if !added.is_empty() {
+            self.hooks()
+                .fire(&HookEvent::SeriesUpdated {
+                    series: url.to_string(),
+                    dir: dir.to_owned(),
+                    added,
+                })
+                .await;
+        }
+        if failed > 0 {
+            self.hooks()
+                .fire(&HookEvent::RunFailed {
+                    series: url.to_string(),
+                    dir: dir.to_owned(),
+                    failed,
+                    errors: errors.into_inner().unwrap_or_default(),
+                })
+                .await;
+        }
+        emit(DownloadEvent::Finished { saved, failed });
+    }
+}
+
+/// Directory name of a chapter, led by its sequence number to keep
+/// reading order.
+fn chapter_name(number: &str, chapter: &Page) -> String {
+    let title = chapter
+        .content
+        .name()
+        .cloned()
+        .or_else(|| chapter.filename())
+        .unwrap_or_default();
+    format!("{} {}", number, title.trim())
+}
+
+/// Tree node of a chapter an earlier run saved, with a placeholder page for
+/// each of its leaves.
+fn known_chapter<F: Fn(&str) -> String>(
+    entry: &ChapterEntry, visual: bool, relocate: F,
+) -> (ContentType, Vec<Page>) {
+    let mut node = Node::new(entry.name.clone());
+    let mut pages = vec![];
+    for source in entry.pages.iter().map(|p| relocate(p)) {
+        let Ok(page) = source.parse::<Page>() else {
+            continue;
+        };
+        let leaf = match visual {
+            true => ContentType::Image(vec![]),
+            false => ContentType::Text(vec![], None),
+        };
+        node.push(source, leaf);
+        pages.push(page);
+    }
+    (ContentType::Chapter(node), pages)
+}
@@ -0,0 +1,68 @@
+use reqwest::Url;
+use std::{fmt, io};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Http(reqwest::Error),
+    Header(String),
+    Cookies(String),
+    /// Path disallowed by the host's robots.txt
+    Disallowed(Url),
+    Login(String),
+    Manifest(String),
+    /// Unreadable `retriever watch` configuration
+    Config(String),
+    Selector(String),
+    Script(String),
+    /// Nothing usable came back from the url
+    NoContent(Url),
+    /// The host answered with a permanent redirect to another host
+    Moved(Url),
+    /// The retriever was cancelled before the request was sent
+    Cancelled,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::Http(e) => write!(f, "http error: {}", e),
+            Error::Header(h) => write!(f, "invalid header: {}", h),
+            Error::Cookies(c) => write!(f, "invalid cookie file: {}", c),
+            Error::Disallowed(u) => write!(f, "disallowed by robots.txt: {}", u),
+            Error::Login(l) => write!(f, "login failed: {}", l),
+            Error::Manifest(m) => write!(f, "invalid site definition: {}", m),
+            Error::Config(c) => write!(f, "invalid watch config: {}", c),
+            Error::Selector(s) => write!(f, "invalid selector: {}", s),
+            Error::Script(s) => write!(f, "script error: {}", s),
+            Error::NoContent(u) => write!(f, "no content at {}", u),
+            Error::Moved(u) => write!(f, "moved permanently to {}", u),
+            Error::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Http(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self { Error::Io(e) }
+}
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self { Error::Http(e) }
+}
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            Error::Cancelled => io::Error::new(io::ErrorKind::Interrupted, e),
+            e => io::Error::other(e),
+        }
+    }
+}
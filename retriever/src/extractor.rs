@@ -1,18 +1,103 @@
-use crate::{page::Page, presets::*, Images, Index, Links, Next, Text, Title};
-use std::fmt::Debug;
+use crate::{
+    error::Error,
+    login::Login,
+    order::ListOrder,
+    page::{ContentType, Page},
+    pagination::Pagination,
+    presets::*,
+    script::Script,
+    search::Search,
+    selector::Selector,
+    series::{SeriesInfo, Status},
+    Images,
+    Index,
+    Links,
+    Next,
+    Series,
+    Text,
+    Title,
+};
+use encoding_rs::Encoding;
+use select::document::Document;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Debug, str::FromStr};
 
 #[derive(Clone)]
 pub struct Extractor {
     title: Option<fn(&Page) -> Title>,
     index: Option<fn(&Page) -> Index>,
     next: Option<fn(&Page) -> Next>,
+    next_page: Option<fn(&Page) -> Next>,
     links: Option<fn(&Page) -> Links>,
     text: Option<fn(&Page) -> Text>,
     images: Option<fn(&Page) -> Images>,
+    series: Option<fn(&Page) -> Series>,
+    rules: BTreeMap<Field, Rule>,
+    charset: Option<&'static Encoding>,
 }
-#[derive(Debug, Clone, Default)]
+/// Extractor fields a site definition can override.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Title,
+    Index,
+    Next,
+    /// Next page within the same chapter
+    #[serde(rename = "next_page")]
+    NextPage,
+    Links,
+    Text,
+    Images,
+    /// Series metadata of an index page, one field per attribute
+    Authors,
+    Artists,
+    Genres,
+    Status,
+    Description,
+    Cover,
+}
+/// Replacement for a preset extractor function.
+#[derive(Debug, Clone)]
+pub enum Rule {
+    Script(Script),
+    /// Text of the matching elements, or `attr` of them. Values are resolved
+    /// against the page url when `absolute` is set.
+    Selector {
+        selector: Selector,
+        attr: Option<String>,
+        absolute: bool,
+    },
+}
+/// Css selector for a field in a site definition.
+///
+/// ```toml
+/// [selectors.links]
+/// select = "ul.chapters > li > a"
+/// attr = "href"
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Pick {
+    pub select: String,
+    /// Attribute to read, the element's text when missing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attr: Option<String>,
+}
+/// A site definition, usually loaded from a toml file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Manifest {
     name: String,
+    hosts: Vec<String>,
+    mirrors: Vec<String>,
+    /// Encoding label overriding what the site's pages declare
+    charset: Option<String>,
+    pagination: Option<Pagination>,
+    /// Direction the index lists chapters in, detected when `auto`
+    order: ListOrder,
+    login: Option<Login>,
+    search: Option<Search>,
+    selectors: BTreeMap<Field, Pick>,
+    scripts: BTreeMap<Field, String>,
     title: usize,
     next_by: usize,
     split_by: usize,
@@ -21,6 +106,7 @@ pub struct Manifest {
     images: usize,
     links: usize,
     index: usize,
+    series: usize,
 }
 
 impl Manifest {
@@ -46,30 +132,282 @@ impl Manifest {
     pub fn links(&self) -> usize { self.links }
 
     pub fn index(&self) -> usize { self.index }
+
+    pub fn series(&self) -> usize { self.series }
+
+    /// Hosts this definition applies to
+    pub fn hosts(&self) -> &[String] { &self.hosts }
+
+    pub fn add_host<T: Into<String>>(&mut self, host: T) -> &mut Self {
+        self.hosts.push(host.into());
+        self
+    }
+
+    /// Other hosts serving the same site, tried when the current one is down
+    pub fn mirrors(&self) -> &[String] { &self.mirrors }
+
+    pub fn add_mirror<T: Into<String>>(&mut self, host: T) -> &mut Self {
+        self.mirrors.push(host.into());
+        self
+    }
+
+    pub fn charset(&self) -> Option<&String> { self.charset.as_ref() }
+
+    pub fn set_charset<T: Into<String>>(&mut self, label: Option<T>) -> &mut Self {
+        self.charset = label.map(Into::into);
+        self
+    }
+
+    pub fn login(&self) -> Option<&Login> { self.login.as_ref() }
+
+    pub fn set_login(&mut self, login: Option<Login>) -> &mut Self {
+        self.login = login;
+        self
+    }
+
+    pub fn search(&self) -> Option<&Search> { self.search.as_ref() }
+
+    pub fn order(&self) -> ListOrder { self.order }
+
+    pub fn set_order(&mut self, order: ListOrder) -> &mut Self {
+        self.order = order;
+        self
+    }
+
+    /// How the chapter list of an index continues on further pages
+    pub fn pagination(&self) -> Option<&Pagination> { self.pagination.as_ref() }
+
+    pub fn set_pagination(&mut self, pagination: Option<Pagination>) -> &mut Self {
+        self.pagination = pagination;
+        self
+    }
+
+    /// Selectors overriding extractor fields, scripts take precedence
+    pub fn selectors(&self) -> &BTreeMap<Field, Pick> { &self.selectors }
+
+    pub fn set_selector(&mut self, field: Field, pick: Option<Pick>) -> &mut Self {
+        match pick {
+            Some(p) => self.selectors.insert(field, p),
+            None => self.selectors.remove(&field),
+        };
+        self
+    }
+
+    /// Rhai sources overriding extractor fields
+    pub fn scripts(&self) -> &BTreeMap<Field, String> { &self.scripts }
+
+    pub fn set_script<T: Into<String>>(&mut self, field: Field, source: Option<T>) -> &mut Self {
+        match source {
+            Some(s) => self.scripts.insert(field, s.into()),
+            None => self.scripts.remove(&field),
+        };
+        self
+    }
+
+    /// Default extractor with this definition's overrides applied.
+    pub fn extractor(&self) -> Result<Extractor, Error> {
+        let mut extractor = Extractor::default();
+        for (field, pick) in &self.selectors {
+            let selector = pick
+                .select
+                .parse::<Selector>()
+                .map_err(|e| Error::Manifest(format!("{}: {}", self.name, e)))?;
+            let rule = Rule::Selector {
+                selector,
+                attr: pick.attr.clone(),
+                absolute: field.is_url(),
+            };
+            extractor.set_rule(*field, Some(rule));
+        }
+        for (field, source) in &self.scripts {
+            let script = Script::new(source.as_str())
+                .map_err(|e| Error::Manifest(format!("{}: {}", self.name, e)))?;
+            extractor.set_rule(*field, Some(Rule::Script(script)));
+        }
+        if let Some(pagination) = &self.pagination {
+            pagination
+                .rule()
+                .map_err(|e| Error::Manifest(format!("{}: {}", self.name, e)))?;
+        }
+        if let Some(label) = &self.charset {
+            let encoding = Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| {
+                Error::Manifest(format!("{}: unknown charset {}", self.name, label))
+            })?;
+            extractor.set_charset(Some(encoding));
+        }
+        Ok(extractor)
+    }
+
+    pub fn set_search(&mut self, search: Option<Search>) -> &mut Self {
+        self.search = search;
+        self
+    }
+}
+impl Field {
+    /// Whether the field's values are links to follow or download
+    pub fn is_url(&self) -> bool {
+        matches!(
+            self,
+            Field::Index
+                | Field::Next
+                | Field::NextPage
+                | Field::Links
+                | Field::Images
+                | Field::Cover
+        )
+    }
+
+    /// Whether the field takes a single value
+    pub fn is_single(&self) -> bool {
+        matches!(
+            self,
+            Field::Title
+                | Field::Index
+                | Field::Next
+                | Field::NextPage
+                | Field::Status
+                | Field::Description
+                | Field::Cover
+        )
+    }
+}
+impl FromStr for Field {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "title" => Ok(Field::Title),
+            "index" => Ok(Field::Index),
+            "next" => Ok(Field::Next),
+            "next_page" | "next-page" => Ok(Field::NextPage),
+            "links" => Ok(Field::Links),
+            "text" => Ok(Field::Text),
+            "images" => Ok(Field::Images),
+            "authors" => Ok(Field::Authors),
+            "artists" => Ok(Field::Artists),
+            "genres" => Ok(Field::Genres),
+            "status" => Ok(Field::Status),
+            "description" => Ok(Field::Description),
+            "cover" => Ok(Field::Cover),
+            other => Err(Error::Manifest(format!("unknown field: {}", other))),
+        }
+    }
+}
+impl FromStr for Manifest {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s).map_err(|e| Error::Manifest(e.to_string()))
+    }
 }
 impl Extractor {
     pub fn new() -> Self {
         Self {
             title: None,
             next: None,
+            next_page: None,
             index: None,
             links: None,
             text: None,
             images: None,
+            series: None,
+            rules: BTreeMap::new(),
+            charset: None,
+        }
+    }
+
+    pub async fn get_title(&self, page: &Page) -> Title {
+        match self.rules.get(&Field::Title) {
+            Some(rule) => rule.string(page),
+            None => self.title.and_then(|f| f(page)),
         }
     }
 
-    pub async fn get_title(&self, page: &Page) -> Title { self.title.and_then(|f| f(page)) }
+    pub async fn get_next(&self, page: &Page) -> Next {
+        match self.rules.get(&Field::Next) {
+            Some(rule) => rule.string(page),
+            None => self.next.and_then(|f| f(page)),
+        }
+    }
+
+    pub async fn get_next_page(&self, page: &Page) -> Next {
+        match self.rules.get(&Field::NextPage) {
+            Some(rule) => rule.string(page),
+            None => self.next_page.and_then(|f| f(page)),
+        }
+    }
 
-    pub async fn get_next(&self, page: &Page) -> Next { self.next.and_then(|f| f(page)) }
+    pub async fn get_index(&self, page: &Page) -> Index {
+        match self.rules.get(&Field::Index) {
+            Some(rule) => rule.string(page),
+            None => self.index.and_then(|f| f(page)),
+        }
+    }
 
-    pub async fn get_index(&self, page: &Page) -> Index { self.index.and_then(|f| f(page)) }
+    pub async fn get_links(&self, page: &Page) -> Links {
+        match self.rules.get(&Field::Links) {
+            Some(rule) => rule.list(page),
+            None => self.links.and_then(|f| f(page)),
+        }
+    }
 
-    pub async fn get_links(&self, page: &Page) -> Links { self.links.and_then(|f| f(page)) }
+    pub async fn get_text(&self, page: &Page) -> Text {
+        match self.rules.get(&Field::Text) {
+            Some(rule) => rule.list(page).map(|t| ContentType::Text(t, None)),
+            None => self.text.and_then(|f| f(page)),
+        }
+    }
 
-    pub async fn get_text(&self, page: &Page) -> Text { self.text.and_then(|f| f(page)) }
+    pub async fn get_images(&self, page: &Page) -> Images {
+        match self.rules.get(&Field::Images) {
+            Some(rule) => rule
+                .list(page)
+                .map(|i| ContentType::Images(i, Some(page.origin()))),
+            None => self.images.and_then(|f| f(page)),
+        }
+    }
 
-    pub async fn get_images(&self, page: &Page) -> Images { self.images.and_then(|f| f(page)) }
+    /// Series metadata of an index page, the attributes rules are given for
+    /// taken from them rather than from the preset function.
+    pub async fn get_series(&self, page: &Page) -> Series {
+        let series = self.series.and_then(|f| f(page));
+        let fields = [
+            Field::Authors,
+            Field::Artists,
+            Field::Genres,
+            Field::Status,
+            Field::Description,
+            Field::Cover,
+        ];
+        if !fields.iter().any(|f| self.rules.contains_key(f)) {
+            return series;
+        }
+        let mut series = series.unwrap_or_else(|| SeriesInfo {
+            url: Some(page.url.to_string()),
+            ..Default::default()
+        });
+        let list = |f: Field| self.rules.get(&f).map(|r| r.list(page).unwrap_or_default());
+        let string = |f: Field| self.rules.get(&f).map(|r| r.string(page));
+        if let Some(authors) = list(Field::Authors) {
+            series.authors = authors;
+        }
+        if let Some(artists) = list(Field::Artists) {
+            series.artists = artists;
+        }
+        if let Some(genres) = list(Field::Genres) {
+            series.genres = genres;
+        }
+        if let Some(status) = string(Field::Status) {
+            series.status = status.map(|s| Status::from(s.as_str())).unwrap_or_default();
+        }
+        if let Some(description) = string(Field::Description) {
+            series.description = description;
+        }
+        if let Some(cover) = string(Field::Cover) {
+            series.cover = cover;
+        }
+        Some(series)
+    }
 
     pub fn set_title(&mut self, f: Option<fn(&Page) -> Title>) { self.title = f; }
 
@@ -77,11 +415,68 @@ impl Extractor {
 
     pub fn set_index(&mut self, f: Option<fn(&Page) -> Next>) { self.index = f; }
 
+    /// Link to the next page of a chapter split over several pages
+    pub fn set_next_page(&mut self, f: Option<fn(&Page) -> Next>) { self.next_page = f; }
+
     pub fn set_links(&mut self, f: Option<fn(&Page) -> Links>) { self.links = f; }
 
     pub fn set_text(&mut self, f: Option<fn(&Page) -> Text>) { self.text = f; }
 
     pub fn set_images(&mut self, f: Option<fn(&Page) -> Images>) { self.images = f; }
+
+    pub fn set_series(&mut self, f: Option<fn(&Page) -> Series>) { self.series = f; }
+
+    /// Overrides `field`, taking precedence over its preset function.
+    pub fn set_rule(&mut self, field: Field, rule: Option<Rule>) {
+        match rule {
+            Some(r) => self.rules.insert(field, r),
+            None => self.rules.remove(&field),
+        };
+    }
+
+    pub fn rule(&self, field: Field) -> Option<&Rule> { self.rules.get(&field) }
+
+    /// Encoding html is decoded with regardless of what the page declares
+    pub fn set_charset(&mut self, charset: Option<&'static Encoding>) { self.charset = charset; }
+
+    pub fn charset(&self) -> Option<&'static Encoding> { self.charset }
+}
+impl Rule {
+    pub fn string(&self, page: &Page) -> Option<String> {
+        match self {
+            Rule::Script(s) => s.string(page),
+            Rule::Selector { .. } => self.select(page)?.into_iter().next(),
+        }
+    }
+
+    pub fn list(&self, page: &Page) -> Option<Vec<String>> {
+        match self {
+            Rule::Script(s) => s.list(page),
+            Rule::Selector { .. } => self.select(page),
+        }
+    }
+
+    fn select(&self, page: &Page) -> Option<Vec<String>> {
+        let Rule::Selector { selector, attr, absolute } = self else {
+            return None;
+        };
+        let doc = Document::from(page.html.as_deref()?);
+        let values = selector
+            .find(&doc)
+            .into_iter()
+            .filter_map(|n| match attr {
+                Some(a) => n.attr(a).map(str::to_owned),
+                None => Some(n.text()),
+            })
+            .map(|v| v.trim().to_owned())
+            .filter(|v| !v.is_empty())
+            .map(|v| match absolute {
+                true => page.url.join(&v).map(|u| u.to_string()).unwrap_or(v),
+                false => v,
+            })
+            .collect::<Vec<_>>();
+        (!values.is_empty()).then_some(values)
+    }
 }
 
 impl Debug for Extractor {
@@ -91,10 +486,14 @@ impl Debug for Extractor {
         f.debug_struct("Extractor")
             .field("title", &self.title.map(|f| type_name_of(f)))
             .field("next", &self.next.map(|f| type_name_of(f)))
+            .field("next_page", &self.next_page.map(|f| type_name_of(f)))
             .field("index", &self.index.map(|f| type_name_of(f)))
             .field("links", &self.links.map(|f| type_name_of(f)))
             .field("text", &self.text.map(|f| type_name_of(f)))
             .field("images", &self.images.map(|f| type_name_of(f)))
+            .field("series", &self.series.map(|f| type_name_of(f)))
+            .field("rules", &self.rules)
+            .field("charset", &self.charset.map(Encoding::name))
             .finish()
     }
 }
@@ -103,10 +502,14 @@ impl Default for Extractor {
         Self {
             title: Some(default_title),
             next: Some(default_next),
+            next_page: None,
             index: Some(default_index),
             links: Some(default_links),
             text: Some(default_text),
             images: Some(default_images),
+            series: Some(default_series),
+            rules: BTreeMap::new(),
+            charset: None,
         }
     }
 }
@@ -0,0 +1,209 @@
+use crate::error::Error;
+#[allow(unused_imports)]
+use log::{debug, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::{Path, PathBuf}, process::Stdio, str::FromStr, time::Duration};
+use tokio::{io::AsyncWriteExt, process::Command, time::timeout};
+
+/// Commands run when things happen during a download.
+///
+/// ```toml
+/// [[hook]]
+/// on = ["series_updated"]
+/// command = "notify-send \"$RETRIEVER_SERIES\" \"$RETRIEVER_ADDED_COUNT new chapters\""
+///
+/// [[hook]]
+/// on = ["chapter_complete", "run_failed"]
+/// command = ["/usr/local/bin/sync-library", "--event"]
+/// stdin = true
+/// timeout = "2m"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Hooks {
+    #[serde(rename = "hook")]
+    pub hooks: Vec<Hook>,
+}
+#[derive(Debug, Clone, Deserialize)]
+pub struct Hook {
+    /// Events the hook runs on
+    pub on: Vec<HookKind>,
+    pub command: CommandLine,
+    /// Write the event as json to the command's stdin, besides setting the
+    /// `RETRIEVER_*` environment variables
+    #[serde(default)]
+    pub stdin: bool,
+    /// Time the command gets before it is killed
+    #[serde(default = "default_timeout", deserialize_with = "crate::watch::interval")]
+    pub timeout: Duration,
+}
+/// A command run through `sh -c`, or a program and its arguments.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum CommandLine {
+    Shell(String),
+    Args(Vec<String>),
+}
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookKind {
+    ChapterComplete,
+    SeriesUpdated,
+    RunFailed,
+}
+/// What a hook is told about, as json on its stdin.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HookEvent {
+    /// Every page of a chapter was saved
+    ChapterComplete {
+        series: String,
+        chapter: String,
+        dir: PathBuf,
+        pages: usize,
+    },
+    /// A download added chapters to the series
+    SeriesUpdated {
+        series: String,
+        dir: PathBuf,
+        added: Vec<String>,
+    },
+    /// A download ended with failures
+    RunFailed {
+        series: String,
+        dir: PathBuf,
+        failed: usize,
+        errors: Vec<String>,
+    },
+}
+
+impl Hooks {
+    pub fn load(path: &Path) -> Result<Self, Error> { fs::read_to_string(path)?.parse() }
+
+    pub fn is_empty(&self) -> bool { self.hooks.is_empty() }
+
+    /// Runs the hooks for `event` one after another. Failures are logged,
+    /// not returned, a hook never stops a download.
+    pub async fn fire(&self, event: &HookEvent) {
+        for hook in self.hooks.iter().filter(|h| h.on.contains(&event.kind())) {
+            match hook.run(event).await {
+                Ok(status) if status.success() => debug!("Hook {} exited with {}", hook, status),
+                Ok(status) => warn!("Hook {} exited with {}", hook, status),
+                Err(e) => warn!("Hook {} failed: {}", hook, e),
+            }
+        }
+    }
+}
+impl FromStr for Hooks {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s).map_err(|e| Error::Config(e.to_string()))
+    }
+}
+impl Hook {
+    /// Runs the command for `event`, killing it after the timeout.
+    pub async fn run(&self, event: &HookEvent) -> std::io::Result<std::process::ExitStatus> {
+        let mut cmd = match &self.command {
+            CommandLine::Shell(line) => {
+                let mut cmd = Command::new("sh");
+                cmd.arg("-c").arg(line);
+                cmd
+            }
+            CommandLine::Args(args) => {
+                let (program, args) = args.split_first().ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidInput, "empty command")
+                })?;
+                let mut cmd = Command::new(program);
+                cmd.args(args);
+                cmd
+            }
+        };
+        cmd.envs(event.env())
+            .stdin(if self.stdin { Stdio::piped() } else { Stdio::null() })
+            .kill_on_drop(true);
+        let mut child = cmd.spawn()?;
+        let run = async {
+            if let Some(mut stdin) = child.stdin.take() {
+                let json = serde_json::to_vec(event)?;
+                // The command may well exit without reading it
+                if let Err(e) = stdin.write_all(&json).await {
+                    debug!("Hook {} didn't take its input: {}", self, e);
+                }
+            }
+            child.wait().await
+        };
+        match timeout(self.timeout, run).await {
+            Ok(status) => status,
+            Err(_) => {
+                let _ = child.kill().await;
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("killed after {:?}", self.timeout),
+                ))
+            }
+        }
+    }
+}
+impl std::fmt::Display for Hook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.command {
+            CommandLine::Shell(line) => write!(f, "{:?}", line),
+            CommandLine::Args(args) => write!(f, "{:?}", args.join(" ")),
+        }
+    }
+}
+impl HookEvent {
+    pub fn kind(&self) -> HookKind {
+        match self {
+            HookEvent::ChapterComplete { .. } => HookKind::ChapterComplete,
+            HookEvent::SeriesUpdated { .. } => HookKind::SeriesUpdated,
+            HookEvent::RunFailed { .. } => HookKind::RunFailed,
+        }
+    }
+
+    /// `RETRIEVER_*` environment variables describing the event.
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        let kind = serde_json::to_value(self.kind()).ok();
+        let name = kind.as_ref().and_then(|k| k.as_str()).unwrap_or_default();
+        let mut env = vec![("RETRIEVER_EVENT", name.to_owned())];
+        let dir = |d: &Path| d.to_string_lossy().into_owned();
+        match self {
+            HookEvent::ChapterComplete {
+                series,
+                chapter,
+                dir: d,
+                pages,
+            } => env.extend([
+                ("RETRIEVER_SERIES", series.clone()),
+                ("RETRIEVER_CHAPTER", chapter.clone()),
+                ("RETRIEVER_DIR", dir(d)),
+                ("RETRIEVER_PAGES", pages.to_string()),
+            ]),
+            HookEvent::SeriesUpdated {
+                series,
+                dir: d,
+                added,
+            } => env.extend([
+                ("RETRIEVER_SERIES", series.clone()),
+                ("RETRIEVER_DIR", dir(d)),
+                ("RETRIEVER_ADDED", added.join("\n")),
+                ("RETRIEVER_ADDED_COUNT", added.len().to_string()),
+            ]),
+            HookEvent::RunFailed {
+                series,
+                dir: d,
+                failed,
+                errors,
+            } => env.extend([
+                ("RETRIEVER_SERIES", series.clone()),
+                ("RETRIEVER_DIR", dir(d)),
+                ("RETRIEVER_FAILED", failed.to_string()),
+                ("RETRIEVER_ERRORS", errors.join("\n")),
+            ]),
+        }
+        env
+    }
+}
+
+fn default_timeout() -> Duration { Duration::from_secs(30) }
@@ -0,0 +1,227 @@
+use crate::{
+    extractor::{Field, Pick, Rule},
+    page::Page,
+    selector::Selector,
+};
+use reqwest::Url;
+use select::{document::Document, node::Node, predicate::Any};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+};
+
+/// Attributes an example value is looked for in, besides element text.
+const ATTRS: [&str; 6] = [
+    "href",
+    "src",
+    "data-src",
+    "data-original",
+    "data-lazy-src",
+    "content",
+];
+/// How many ancestors of the example element are tried as anchors.
+const DEPTH: usize = 4;
+
+/// A selector proposed for a field, with what it yields on the sample page.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Candidate {
+    pub pick: Pick,
+    /// Ids, classes and attributes, type selectors, as css counts them
+    pub specificity: (usize, usize, usize),
+    pub values: Vec<String>,
+}
+
+impl Candidate {
+    pub fn matches(&self) -> usize { self.values.len() }
+
+    /// Site definition fragment setting `field` to this candidate.
+    pub fn snippet(&self, field: Field) -> String {
+        let fragment = BTreeMap::from([("selectors", BTreeMap::from([(field, &self.pick)]))]);
+        toml::to_string(&fragment).unwrap_or_default()
+    }
+}
+
+/// Proposes selectors for `field` that reproduce `example` on a page saved
+/// from `base`, best first.
+///
+/// The example can be the element's text or one of its link attributes, for
+/// those relative and absolute forms are equivalent. Candidates are built
+/// from the example element and its classed or identified ancestors,
+/// classes and ids containing digits are skipped as likely generated. List
+/// fields favour selectors that also pick up siblings of the example,
+/// single value fields ones that match exactly once, ties go to the more
+/// specific selector and then to the one matching more.
+pub fn infer(html: &str, base: &Url, example: &str, field: Field) -> Vec<Candidate> {
+    let doc = Document::from(html);
+    let wanted = normalize(base, example.trim(), field.is_url());
+    let text = normalize(base, example, false);
+    let mut picks = HashMap::new();
+    for node in doc.find(Any).filter(|n| n.name().is_some()) {
+        let attr = ATTRS.iter().find(|a| {
+            node.attr(a)
+                .is_some_and(|v| normalize(base, v.trim(), true) == wanted)
+        });
+        let attr = match attr {
+            Some(a) => Some(a.to_string()),
+            None if innermost_text(node).as_ref() == Some(&text) => None,
+            None => continue,
+        };
+        for (select, specificity) in selectors_for(node) {
+            picks.entry(Pick {
+                select,
+                attr: attr.clone(),
+            })
+            .or_insert(specificity);
+        }
+    }
+    let mut page = Page::from(base.clone());
+    page.html = Some(html.to_owned());
+    // Of selectors yielding the same values keep the simplest
+    let mut best: HashMap<Vec<String>, Candidate> = HashMap::new();
+    for (pick, specificity) in picks {
+        let Ok(selector) = pick.select.parse::<Selector>() else {
+            continue;
+        };
+        let rule = Rule::Selector {
+            selector,
+            attr: pick.attr.clone(),
+            absolute: field.is_url(),
+        };
+        let Some(values) = rule.list(&page) else {
+            continue;
+        };
+        if !values
+            .iter()
+            .any(|v| normalize(base, v, field.is_url()) == wanted)
+        {
+            continue;
+        }
+        let candidate = Candidate {
+            pick,
+            specificity,
+            values: values.clone(),
+        };
+        match best.get(&values) {
+            Some(c) if simpler(c, &candidate) => (),
+            _ => {
+                best.insert(values, candidate);
+            }
+        }
+    }
+    let mut candidates = best.into_values().collect::<Vec<_>>();
+    candidates.sort_by_key(|c| {
+        let misfit = match field.is_single() {
+            true => c.matches() != 1,
+            false => c.matches() < 2,
+        };
+        (
+            misfit,
+            Reverse(c.specificity),
+            Reverse(c.matches()),
+            c.pick.select.len(),
+        )
+    });
+    candidates
+}
+
+fn simpler(a: &Candidate, b: &Candidate) -> bool {
+    let key = |c: &Candidate| (c.specificity, c.pick.select.len(), c.pick.select.clone());
+    key(a) <= key(b)
+}
+
+fn normalize(base: &Url, value: &str, url: bool) -> String {
+    match url {
+        true => base
+            .join(value)
+            .map(|u| u.to_string())
+            .unwrap_or_else(|_| value.to_owned()),
+        false => value.split_whitespace().collect::<Vec<_>>().join(" "),
+    }
+}
+
+/// The node's text, unless a child element carries the same text.
+fn innermost_text(node: Node) -> Option<String> {
+    let text = node.text().split_whitespace().collect::<Vec<_>>().join(" ");
+    let nested = node
+        .children()
+        .filter(|c| c.name().is_some())
+        .any(|c| c.text().split_whitespace().collect::<Vec<_>>().join(" ") == text);
+    (!text.is_empty() && !nested).then_some(text)
+}
+
+fn stable(token: &str) -> bool {
+    !token.is_empty()
+        && token
+            .chars()
+            .all(|c| c.is_ascii_alphabetic() || c == '-' || c == '_')
+}
+
+fn classes(node: Node<'_>) -> Vec<&str> {
+    node.attr("class")
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter(|c| stable(c))
+        .collect()
+}
+
+/// Compounds selecting the example element itself.
+fn own(node: Node) -> Vec<(String, (usize, usize, usize))> {
+    let Some(name) = node.name() else {
+        return vec![];
+    };
+    let mut out = vec![(name.to_owned(), (0, 0, 1))];
+    out.extend(
+        classes(node)
+            .into_iter()
+            .map(|c| (format!("{}.{}", name, c), (0, 1, 1))),
+    );
+    if let Some(id) = node.attr("id").filter(|id| stable(id)) {
+        out.push((format!("{}#{}", name, id), (1, 0, 1)));
+    }
+    out
+}
+
+/// Compounds anchoring an ancestor, plain tags only for the direct parent.
+fn anchors(node: Node, parent: bool) -> Vec<(String, (usize, usize, usize))> {
+    let Some(name) = node.name() else {
+        return vec![];
+    };
+    if matches!(name, "html" | "body") {
+        return vec![];
+    }
+    let mut out = vec![];
+    if parent {
+        out.push((name.to_owned(), (0, 0, 1)));
+    }
+    out.extend(
+        classes(node)
+            .into_iter()
+            .map(|c| (format!("{}.{}", name, c), (0, 1, 1))),
+    );
+    if let Some(id) = node.attr("id").filter(|id| stable(id)) {
+        out.push((format!("#{}", id), (1, 0, 0)));
+    }
+    out
+}
+
+fn selectors_for(node: Node) -> Vec<(String, (usize, usize, usize))> {
+    let own = own(node);
+    let mut out = own.clone();
+    let mut ancestor = node.parent();
+    for depth in 0..DEPTH {
+        let Some(a) = ancestor else {
+            break;
+        };
+        for (anchor, (ai, ac, at)) in anchors(a, depth == 0) {
+            for (compound, (i, c, t)) in &own {
+                let specificity = (ai + i, ac + c, at + t);
+                out.push((format!("{} {}", anchor, compound), specificity));
+                if depth == 0 {
+                    out.push((format!("{} > {}", anchor, compound), specificity));
+                }
+            }
+        }
+        ancestor = a.parent();
+    }
+    out
+}
@@ -1,12 +1,35 @@
 #![feature(associated_type_defaults)]
 #![feature(iter_advance_by)]
 
+pub mod bandwidth;
+pub mod canonical;
+pub mod chapters;
+pub mod charset;
+pub mod client;
+pub mod dedup;
+pub mod doctor;
+pub mod download;
+pub mod error;
 pub mod extractor;
+pub mod hooks;
+pub mod infer;
+pub mod login;
+pub mod order;
 pub mod page;
+pub mod pagination;
 pub mod presets;
+pub mod resume;
 pub mod retriever;
+pub mod robots;
+pub mod script;
+pub mod search;
+pub mod selector;
+pub mod series;
+pub mod stats;
+pub mod watch;
 
 use page::ContentType;
+use series::SeriesInfo;
 
 type Title = Option<String>;
 type Index = Option<String>;
@@ -14,3 +37,4 @@ type Next = Option<String>;
 type Links = Option<Vec<String>>;
 type Text = Option<ContentType>;
 type Images = Option<ContentType>;
+type Series = Option<SeriesInfo>;
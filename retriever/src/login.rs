@@ -0,0 +1,109 @@
+use crate::{client::config_dir, error::Error, page::Page};
+#[allow(unused_imports)]
+use log::{debug, info, trace};
+use reqwest::Url;
+use select::{
+    document::Document,
+    node::Node,
+    predicate::{And, Attr, Name},
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, env, fs};
+
+/// Form based login declared by a site definition.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Login {
+    /// Page holding the login form
+    pub url: String,
+    /// Where the form is posted, defaults to the form's `action`
+    pub action: Option<String>,
+    pub username_field: String,
+    pub password_field: String,
+    /// Extra fields posted along with the hidden inputs of the form
+    pub fields: BTreeMap<String, String>,
+    /// Environment variables holding the credentials, the keyring file is
+    /// used when they are unset
+    pub username_env: Option<String>,
+    pub password_env: Option<String>,
+    /// Text present in the response only when the login worked
+    pub success: Option<String>,
+    /// Cookie set only when the login worked
+    pub success_cookie: Option<String>,
+    /// Text present only once the session has expired, defaults to the
+    /// password input of the login form
+    pub expired: Option<String>,
+}
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Login {
+    /// Credentials for `site`, from the environment or from the `[site]`
+    /// table of `keyring.toml` in the config directory.
+    pub fn credentials(&self, site: &str) -> Result<Credentials, Error> {
+        let var = |name: &Option<String>| name.as_ref().and_then(|n| env::var(n).ok());
+        if let (Some(username), Some(password)) = (var(&self.username_env), var(&self.password_env))
+        {
+            return Ok(Credentials { username, password });
+        }
+        let path = config_dir()
+            .map(|d| d.join("keyring.toml"))
+            .ok_or_else(|| Error::Login(format!("no credentials for {}", site)))?;
+        let mut keyring: BTreeMap<String, Credentials> = toml::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| Error::Login(format!("{:?}: {}", path, e)))?;
+        keyring
+            .remove(site)
+            .ok_or_else(|| Error::Login(format!("no credentials for {} in {:?}", site, path)))
+    }
+
+    /// Fields to post: the hidden inputs of the form (csrf tokens and the
+    /// like), `fields`, and the credentials.
+    pub fn form(&self, page: &Document, creds: &Credentials) -> BTreeMap<String, String> {
+        let mut form = self
+            .form_node(page)
+            .map(|f| {
+                f.find(And(Name("input"), Attr("type", "hidden")))
+                    .filter_map(|i| Some((i.attr("name")?.to_owned(), i.attr("value")?.to_owned())))
+                    .collect::<BTreeMap<_, _>>()
+            })
+            .unwrap_or_default();
+        form.extend(self.fields.clone());
+        form.insert(self.username_field.clone(), creds.username.clone());
+        form.insert(self.password_field.clone(), creds.password.clone());
+        form
+    }
+
+    /// Url the form is posted to.
+    pub fn target(&self, page: &Document, base: &Url) -> Result<Url, Error> {
+        let action = self
+            .action
+            .as_deref()
+            .or_else(|| self.form_node(page).and_then(|f| f.attr("action")))
+            .unwrap_or(base.as_str());
+        base.join(action)
+            .map_err(|_| Error::Login(format!("bad form action: {}", action)))
+    }
+
+    /// Whether `page` was served instead of the content because the session
+    /// is gone.
+    pub fn expired(&self, page: &Page) -> bool {
+        let marker = self
+            .expired
+            .clone()
+            .unwrap_or_else(|| format!("name=\"{}\"", self.password_field));
+        page.html.as_ref().is_some_and(|h| h.contains(&marker))
+    }
+
+    fn form_node<'a>(&self, page: &'a Document) -> Option<Node<'a>> {
+        let mut node = page
+            .find(And(Name("input"), Attr("name", self.password_field.as_str())))
+            .next()?;
+        while node.name() != Some("form") {
+            node = node.parent()?;
+        }
+        Some(node)
+    }
+}
@@ -0,0 +1,118 @@
+#[allow(unused_imports)]
+use log::{debug, info, trace, warn};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// Words a chapter number follows in urls, longest first.
+const KEYWORDS: &[&str] = &["chapter", "episode", "chap", "ch", "ep"];
+
+/// Direction an index lists its chapters in.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListOrder {
+    /// Worked out from the chapter numbers
+    #[default]
+    Auto,
+    /// Oldest first, reading order
+    Ascending,
+    /// Newest first
+    Descending,
+}
+
+impl ListOrder {
+    /// Direction of `chapters`, by whether their numbers mostly rise or
+    /// fall. Lists without enough numbers are taken as ascending.
+    pub fn detect<T: AsRef<str>>(chapters: &[T]) -> Self {
+        let numbers = chapters
+            .iter()
+            .filter_map(|c| chapter_number(c.as_ref()))
+            .collect::<Vec<_>>();
+        let (mut up, mut down) = (0, 0);
+        for w in numbers.windows(2) {
+            if w[1] > w[0] {
+                up += 1;
+            } else if w[1] < w[0] {
+                down += 1;
+            }
+        }
+        trace!("{} rising, {} falling chapter numbers", up, down);
+        if down > up {
+            ListOrder::Descending
+        } else {
+            ListOrder::Ascending
+        }
+    }
+}
+
+/// `chapters` as listed in `order`, put into reading order.
+pub fn reading_order<T: AsRef<str>>(mut chapters: Vec<T>, order: ListOrder) -> Vec<T> {
+    let order = match order {
+        ListOrder::Auto => ListOrder::detect(&chapters),
+        o => o,
+    };
+    if order == ListOrder::Descending {
+        debug!("Chapters are listed newest first, reversing");
+        chapters.reverse();
+    }
+    chapters
+}
+
+/// Chapter number in a url or title: the number after a word like
+/// "chapter" or "ch", else the last number of the path. Fractional numbers
+/// like `12.5` are kept.
+pub fn chapter_number(s: &str) -> Option<f64> {
+    let text = match s.parse::<Url>() {
+        Ok(url) => url.path().to_lowercase(),
+        Err(_) => s.to_lowercase(),
+    };
+    let after = |kw: &str| {
+        text.rmatch_indices(kw).find_map(|(i, _)| {
+            let rest = text[i + kw.len()..].trim_start_matches(['-', '_', ' ', '.', '/']);
+            number_at(rest)
+        })
+    };
+    KEYWORDS.iter().find_map(|kw| after(kw)).or_else(|| {
+        let end = text.rfind(|c: char| c.is_ascii_digit())?;
+        let start = text[..=end]
+            .rfind(|c: char| !c.is_ascii_digit() && c != '.')
+            .map_or(0, |i| i + 1);
+        number_at(text[start..=end].trim_start_matches('.'))
+    })
+}
+
+/// Names numbering chapters given in reading order: their own chapter
+/// numbers when those rise steadily through the list, their positions
+/// otherwise. Either way a chapter keeps its name as the series grows.
+pub fn sequence<T: AsRef<str>>(chapters: &[T]) -> Vec<String> {
+    let numbers = chapters
+        .iter()
+        .map(|c| chapter_number(c.as_ref()))
+        .collect::<Option<Vec<_>>>();
+    match numbers {
+        Some(n) if n.windows(2).all(|w| w[0] < w[1]) => n.into_iter().map(label).collect(),
+        _ => (1..=chapters.len()).map(|i| format!("{:03}", i)).collect(),
+    }
+}
+
+/// The number at the start of `s`, with at most one decimal point.
+fn number_at(s: &str) -> Option<f64> {
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let int = digits(s);
+    if int == 0 {
+        return None;
+    }
+    let frac = match s[int..].strip_prefix('.') {
+        Some(rest) if digits(rest) > 0 => digits(rest) + 1,
+        _ => 0,
+    };
+    s[..int + frac].parse().ok()
+}
+
+/// `12` as `012`, `12.5` as `012.5`.
+fn label(n: f64) -> String {
+    let s = n.to_string();
+    match s.split_once('.') {
+        Some((whole, frac)) => format!("{:0>3}.{}", whole, frac),
+        None => format!("{:0>3}", s),
+    }
+}
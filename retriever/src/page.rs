@@ -1,25 +1,45 @@
-use crate::{extractor::Extractor, Index, Links, Next, Title};
+use crate::{
+    bandwidth::Bandwidth,
+    canonical::{canonical_link, normalize, page_key},
+    charset::decode,
+    dedup::content_hash,
+    error::Error,
+    extractor::Extractor,
+    resume::PART,
+    series::SeriesInfo,
+    stats::Stats,
+    Index,
+    Links,
+    Next,
+    Title,
+};
+use futures::future::{BoxFuture, FutureExt};
 #[allow(unused_imports)]
 use log::{debug, info, trace};
 use reqwest::{
-    header::{HeaderValue, REFERER},
+    header::{HeaderValue, CONTENT_TYPE, LOCATION, REFERER},
     Client,
+    Response,
     Url,
 };
 use select::document::Document;
 use std::{
     borrow::Cow,
+    collections::HashSet,
     convert::TryFrom,
     fmt::Debug,
     ops::Deref,
-    path::Path,
+    path::{Path, PathBuf},
     str::FromStr,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use time::OffsetDateTime;
-use tokio::{fs::write, io};
+use tokio::{
+    fs::{remove_file, rename, write},
+    io,
+};
+use tokio_util::sync::CancellationToken;
 use url::ParseError;
-use uuid::Uuid;
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Page<T = fn() -> String> {
@@ -30,23 +50,43 @@ pub struct Page<T = fn() -> String> {
     pub content: Content,
     pub referer: Option<HeaderValue>,
     pub last: Option<OffsetDateTime>,
+    /// Where the page says it canonically lives, on its own host. Only told
+    /// apart by, see `key`; requests keep going to `url`.
+    pub canonical: Option<Url>,
 }
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
 pub enum ContentType {
     Text(Vec<String>, Option<String>),
     Image(Vec<u8>),
-    Chapter(Vec<ContentType>),
+    /// Root of the series tree
+    Series(Node),
+    Volume(Node),
+    Chapter(Node),
     Images(Vec<String>, Option<String>),
     Chapters(Vec<String>),
     #[default]
     Empty,
 }
+/// Name of the file listing where the contents of a directory came from.
+pub const SOURCES: &str = "sources.lst";
+
+/// A level of the series tree. Saved as a directory holding its children,
+/// with a `sources.lst` listing the urls they came from.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct Node {
+    pub name: String,
+    /// Url of each child, empty for volumes
+    pub sources: Vec<String>,
+    pub children: Vec<ContentType>,
+}
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
 pub struct Content {
     name: Title,
     index: Index,
     next: Next,
+    next_page: Next,
     links: Links,
+    series: Option<SeriesInfo>,
     pub data: Option<ContentType>,
 }
 
@@ -61,9 +101,15 @@ impl Page {
         self
     }
 
+    /// Downloads the page and runs `extractor` over it, recording the
+    /// request in `stats` and reading the body within `bandwidth`. Gives up
+    /// without touching the page if `cancel` fires before the response
+    /// arrives, a body already coming in is read to the end. Fails when the
+    /// host can't be reached or has moved permanently to another host.
     pub async fn visit(
-        &mut self, client: Client, extractor: &Extractor, visual: bool,
-    ) -> &mut Self {
+        &mut self, client: Client, extractor: &Extractor, visual: bool, stats: &Stats,
+        cancel: &CancellationToken, bandwidth: Option<&Bandwidth>,
+    ) -> Result<&mut Self, Error> {
         info!("Visited: {}", self.url.as_str());
         let req = client
             .get(self.url.as_ref())
@@ -75,38 +121,75 @@ impl Page {
             )
             .build()
             .unwrap_or_else(|_| panic!("Failed to build request for: {}", &self.url));
-        let page = client
-            .execute(req.try_clone().expect("No streaming sources allowed"))
-            .await
-            .expect("Failed to unwrap response");
+        let host = self.host().unwrap_or_default();
+        let mut start = Instant::now();
+        let page = tokio::select! {
+            res = client.execute(req.try_clone().expect("No streaming sources allowed")) => res?,
+            _ = cancel.cancelled() => {
+                debug!("Cancelled before {} answered", self.url);
+                return Ok(self);
+            }
+        };
         self.last = Some(OffsetDateTime::now_utc());
+        let mut status = page.status().as_u16();
+        if matches!(status, 301 | 308) {
+            stats.response(&host, status, 0, start.elapsed());
+            let to = page
+                .headers()
+                .get(LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .and_then(|l| self.url.join(l).ok());
+            if let Some(to) = to {
+                return Err(Error::Moved(to));
+            }
+        }
         if let Some(ContentType::Image(ref mut data)) = self.content.data {
-            let mut bytes = page.bytes().await;
+            let mut bytes = read_body(page, bandwidth).await;
             // Retry once
             if bytes.is_err() {
+                stats.response(&host, status, 0, start.elapsed());
+                stats.retry(&host);
                 tokio::time::sleep(Duration::from_millis(2000)).await;
-                let page = client
-                    .execute(req)
-                    .await
-                    .expect("Failed to unwrap response");
+                start = Instant::now();
+                let page = client.execute(req).await?;
                 self.last = Some(OffsetDateTime::now_utc());
-                bytes = page.bytes().await;
+                status = page.status().as_u16();
+                bytes = read_body(page, bandwidth).await;
             }
-            *data = bytes.unwrap().to_vec();
+            let bytes = bytes?;
+            stats.response(&host, status, bytes.len() as u64, start.elapsed());
+            *data = bytes;
             trace!("Early return, Image");
-            return self;
+            return Ok(self);
         };
-        self.html = Some(page.text().await.expect("Failed to get html source code"));
+        let charset = page
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|ct| ct.split(';').find_map(|p| p.trim().strip_prefix("charset=")))
+            .map(|c| c.trim_matches('"').to_owned());
+        let body = read_body(page, bandwidth).await?;
+        stats.response(&host, status, body.len() as u64, start.elapsed());
+        self.html = Some(decode(&body, charset.as_deref(), extractor.charset()));
         trace!("html: {:?}", &self.html);
+        let canonical = self.doc().and_then(|d| canonical_link(&d, &self.url));
+        self.canonical = canonical.filter(|u| u.host() == self.url.host() && *u != self.url);
+        if let Some(url) = &self.canonical {
+            debug!("{} is canonically {}", self.url, url);
+        }
         self.content.name = extractor.get_title(self).await;
         trace!("name: {:?}", &self.content.name);
         self.content.index = extractor.get_index(self).await;
         trace!("index: {:?}", &self.content.index);
         self.content.next = extractor.get_next(self).await;
         trace!("next: {:?}", &self.content.next);
+        self.content.next_page = extractor.get_next_page(self).await;
+        trace!("next_page: {:?}", self.content.next_page);
         self.content.links = extractor.get_links(self).await;
         trace!("links: {:?}", &self.content.links.as_ref().map(|l| l.len()));
         trace!("links: {:?}", &self.content.links.as_ref().map(|l| &l[..]));
+        self.content.series = extractor.get_series(self).await;
+        trace!("series: {:?}", self.content.series);
         self.content.data = if visual {
             extractor.get_images(self).await
         } else {
@@ -114,10 +197,13 @@ impl Page {
         };
         trace!("data: {:?}", &self.content.data);
         // TODO: convert and assign to Content
-        self.empty();
-        self
+        Ok(self)
     }
 
+    /// What the page is told apart by: the `page_key` of its canonical url,
+    /// of the url it was requested at if it has none.
+    pub fn key(&self) -> String { page_key(self.canonical.as_ref().unwrap_or(&self.url).as_str()) }
+
     pub fn doc(&self) -> Option<Document> { self.html.as_ref().map(|s| Document::from(s.as_str())) }
 
     pub fn name(&self) -> &str {
@@ -168,6 +254,25 @@ impl Page {
     }
 
     pub fn empty(&mut self) { self.html = None; }
+
+    /// Where the page's next link leads, resolved against the page. `None`
+    /// for placeholders like `#`, `javascript:` links or the site root.
+    pub fn next_url(&self) -> Option<Url> { self.link(self.content.next.as_deref()?) }
+
+    /// Where the link to the next page of the same chapter leads, see
+    /// `next_url`.
+    pub fn next_page_url(&self) -> Option<Url> { self.link(self.content.next_page.as_deref()?) }
+
+    fn link(&self, href: &str) -> Option<Url> {
+        let href = href.trim();
+        if href.is_empty() || href.starts_with('#') {
+            return None;
+        }
+        let url = normalize(self.url.join(href).ok()?);
+        let placeholder = !matches!(url.scheme(), "http" | "https") ||
+            (url.path() == "/" && url.query().is_none());
+        (!placeholder).then_some(url)
+    }
 }
 impl Content {
     pub fn name(&self) -> Option<&String> { self.name.as_ref() }
@@ -181,14 +286,19 @@ impl Content {
 
     pub fn next(&self) -> &Next { &self.next }
 
+    /// Link to the next page of the same chapter
+    pub fn next_page(&self) -> &Next { &self.next_page }
+
     pub fn links(&self) -> &Links { &self.links }
 
+    pub fn series(&self) -> Option<&SeriesInfo> { self.series.as_ref() }
+
     pub async fn save(&self, pb: &Path) -> io::Result<()> {
         let name_from = |cnt: &[u8]| -> String {
             let res = self
                 .name
                 .as_ref()
-                .unwrap_or(&Uuid::new_v5(&Uuid::NAMESPACE_OID, cnt).to_string())
+                .unwrap_or(&content_hash(cnt).to_string())
                 .to_owned();
             trace!("generated new name: {}", res);
             res
@@ -202,7 +312,7 @@ impl Content {
                 // z = z.join(name_from(&contents));
                 // let p = pb.join(name_from(&contents[..]));
                 // trace!("final text path: {:?}", p);
-                write(z, contents).await?;
+                write_atomic(&z, &contents).await?;
             }
             Some(ContentType::Image(data)) => {
                 let mut z = pb.to_path_buf();
@@ -231,26 +341,223 @@ impl Content {
                 let mut pb = pb.to_path_buf();
                 pb.set_file_name(filename);
                 trace!("final image path: {:?}", &pb);
-                write(pb, data).await?;
+                write_atomic(&pb, data).await?;
+            }
+            Some(tree) if tree.node().is_some() => tree.save(pb).await?,
+            Some(ContentType::Images(urls, _) | ContentType::Chapters(urls)) => {
+                tokio::fs::create_dir_all(pb).await?;
+                write_atomic(&pb.join(SOURCES), sources_lst(urls).as_bytes()).await?;
             }
             _ => (),
-            // Some(ContentType::Images(data, _)) => {
-            //     let contents = data[..].join("\n");
-            //     let mut p = pb.join(name_from(contents.as_bytes())).join("sources");
-            //     p.set_extension(".lst");
-            //     write(p, contents).await?;
-            // }
-            // Some(ContentType::Chapters(data)) => {
-            //     let contents = data[..].join("\n");
-            //     let mut p = pb.join(name_from(contents.as_bytes())).join("sources");
-            //     p.set_extension(".lst");
-            //     write(p, contents).await?;
-            // }
         };
         Ok(())
     }
 }
+impl Node {
+    pub fn new<T: Into<String>>(name: T) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn push<T: Into<String>>(&mut self, source: T, child: ContentType) -> &mut Self {
+        self.sources.push(source.into());
+        self.children.push(child);
+        self
+    }
+}
+
+fn sources_lst(urls: &[String]) -> String {
+    urls.iter()
+        .filter(|u| !u.is_empty())
+        .fold(String::new(), |acc, u| acc + u.as_str() + "\n")
+}
+
+/// Directory name for a tree level, without path separators or characters
+/// Windows refuses.
+pub fn dir_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    match name.trim_matches(|c: char| c.is_whitespace() || c == '.') {
+        "" => "_".to_owned(),
+        name => name.to_owned(),
+    }
+}
+
+/// File name of the `i`th child, numbered so files sort in reading order
+/// and typed after the url it came from.
+fn leaf_name(i: usize, leaf: &ContentType, source: &str) -> String {
+    let ext = match leaf {
+        ContentType::Text(..) => "txt".to_owned(),
+        _ => Url::parse(source)
+            .ok()
+            .and_then(|u| {
+                let last = u.path_segments()?.next_back()?.to_owned();
+                Some(last.rsplit_once('.')?.1.to_ascii_lowercase())
+            })
+            .filter(|e| {
+                (1..=5).contains(&e.len()) && e.chars().all(|c| c.is_ascii_alphanumeric())
+            })
+            .unwrap_or_else(|| "bin".to_owned()),
+    };
+    format!("{:03}.{}", i + 1, ext)
+}
+
+/// Volume number named in a chapter title or url, as in "Vol. 2 Ch. 10"
+/// or "/volume-2/chapter-10".
+pub fn volume_of(s: &str) -> Option<u32> {
+    let s = s.to_lowercase();
+    s.match_indices("vol")
+        .filter(|(i, _)| !s[..*i].ends_with(char::is_alphabetic))
+        .find_map(|(i, _)| {
+            let rest = s[i + 3..].trim_start_matches(|c: char| {
+                c.is_alphabetic() || c.is_whitespace() || matches!(c, '.' | '-' | '_')
+            });
+            let digits = rest
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect::<String>();
+            digits.parse().ok()
+        })
+}
+
+/// Reads the whole body, within `bandwidth` when there is a limit.
+async fn read_body(res: Response, bandwidth: Option<&Bandwidth>) -> reqwest::Result<Vec<u8>> {
+    match bandwidth {
+        Some(b) => b.read(res).await,
+        None => Ok(res.bytes().await?.to_vec()),
+    }
+}
+
+/// Writes through a `.part` file renamed into place, so an interrupted run
+/// never leaves a truncated file under the final name.
+async fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(PART);
+    let tmp = PathBuf::from(tmp);
+    if let Err(e) = write(&tmp, data).await {
+        let _ = remove_file(&tmp).await;
+        return Err(e);
+    }
+    rename(&tmp, path).await
+}
 impl ContentType {
+    /// Series tree of `chapters` given with their urls, in reading order.
+    /// Consecutive chapters whose name or url mentions the same volume are
+    /// grouped under it.
+    pub fn series<T: Into<String>>(name: T, chapters: Vec<(String, ContentType)>) -> Self {
+        let mut root = Node::new(name);
+        let mut volume: Option<(u32, Node)> = None;
+        for (url, chapter) in chapters {
+            let name = chapter.node().map(|n| n.name.as_str()).unwrap_or_default();
+            let number = volume_of(name).or_else(|| volume_of(&url));
+            match (&mut volume, number) {
+                (Some((v, node)), Some(n)) if *v == n => {
+                    node.push(url, chapter);
+                    continue;
+                }
+                _ => (),
+            }
+            if let Some((_, node)) = volume.take() {
+                root.push("", ContentType::Volume(node));
+            }
+            match number {
+                Some(n) => {
+                    let mut node = Node::new(format!("Volume {}", n));
+                    node.push(url, chapter);
+                    volume = Some((n, node));
+                }
+                None => {
+                    root.push(url, chapter);
+                }
+            }
+        }
+        if let Some((_, node)) = volume {
+            root.push("", ContentType::Volume(node));
+        }
+        ContentType::Series(root)
+    }
+
+    /// The tree level, if this is one.
+    pub fn node(&self) -> Option<&Node> {
+        match self {
+            ContentType::Series(n) | ContentType::Volume(n) | ContentType::Chapter(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn node_mut(&mut self) -> Option<&mut Node> {
+        match self {
+            ContentType::Series(n) | ContentType::Volume(n) | ContentType::Chapter(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Every page and text of the tree in reading order, with its path
+    /// relative to the tree's directory and its url.
+    pub fn leaves(&self) -> Vec<(PathBuf, String)> {
+        fn walk(content: &ContentType, at: &Path, out: &mut Vec<(PathBuf, String)>) {
+            let Some(node) = content.node() else {
+                return;
+            };
+            for (i, child) in node.children.iter().enumerate() {
+                let source = node.sources.get(i).map(String::as_str).unwrap_or_default();
+                match child.node() {
+                    Some(n) => walk(child, &at.join(dir_name(&n.name)), out),
+                    None => out.push((at.join(leaf_name(i, child, source)), source.to_owned())),
+                }
+            }
+        }
+        let mut out = vec![];
+        walk(self, Path::new(""), &mut out);
+        out
+    }
+
+    /// Writes the tree into `dir`: a directory per volume and chapter, a
+    /// `sources.lst` in each and every page or text that holds data.
+    pub fn save<'a>(&'a self, dir: &'a Path) -> BoxFuture<'a, io::Result<()>> {
+        async move {
+            let Some(node) = self.node() else {
+                return Ok(());
+            };
+            tokio::fs::create_dir_all(dir).await?;
+            write_atomic(&dir.join(SOURCES), sources_lst(&node.sources).as_bytes()).await?;
+            for (i, child) in node.children.iter().enumerate() {
+                match child.node() {
+                    Some(n) => {
+                        let sub = dir.join(dir_name(&n.name));
+                        child.save(&sub).await?;
+                    }
+                    None => {
+                        let source = node.sources.get(i).map(String::as_str).unwrap_or_default();
+                        child.write(&dir.join(leaf_name(i, child, source))).await?;
+                    }
+                }
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    /// Writes a page or text to `path`, skipping it while it holds no data.
+    pub async fn write(&self, path: &Path) -> io::Result<()> {
+        let data = self.as_data();
+        if data.is_empty() {
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        write_atomic(path, &data).await
+    }
+
     pub fn as_data(&self) -> Cow<'_, [u8]> {
         match self {
             ContentType::Text(data, delim) => {
@@ -260,10 +567,6 @@ impl ContentType {
                     .into()
             }
             ContentType::Image(data) => data.into(),
-            // ContentType::Chapter(_) => todo!(),
-            // ContentType::Images(_, _) => todo!(),
-            // ContentType::Chapters(_) => todo!(),
-            // ContentType::Empty => todo!(),
             _ => Cow::default(),
         }
     }
@@ -271,6 +574,7 @@ impl ContentType {
     pub fn to_pages(&self) -> Option<Vec<Page>> {
         fn convert(input: &[String]) -> Vec<Page> {
             trace!("input was: {:?}", input);
+            let mut seen = HashSet::new();
             input
                 .iter()
                 .filter_map(|p| {
@@ -281,6 +585,7 @@ impl ContentType {
                         q
                     })
                 })
+                .filter(|p| seen.insert(page_key(p.url.as_str())))
                 .collect()
         }
         match self {
@@ -313,7 +618,9 @@ impl From<Vec<u8>> for Content {
             name: None,
             index: None,
             next: None,
+            next_page: None,
             links: None,
+            series: None,
             data: Some(ContentType::Image(data)),
         }
     }
@@ -337,65 +644,39 @@ impl Default for Page {
             content: Default::default(),
             referer: Default::default(),
             last: Default::default(),
+            canonical: Default::default(),
         }
     }
 }
 impl Iterator for Page {
     type Item = Page;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let s = self.content.next.as_ref()?; // .and_then(|s| s.try_into().ok())
-        let out: Option<Page> = s.try_into().ok();
-        if out.is_none() {
-            return (self.origin() + s).try_into().ok();
-        };
-        out
-    }
+    fn next(&mut self) -> Option<Self::Item> { self.next_url().map(Page::from) }
 }
 impl FromStr for Page {
     type Err = ParseError;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self {
-            url: s.parse()?,
-            ..Default::default()
-        })
-    }
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Ok(s.parse::<Url>()?.into()) }
 }
 impl TryFrom<String> for Page {
     type Error = ParseError;
 
-    fn try_from(s: String) -> Result<Self, Self::Error> {
-        Ok(Self {
-            url: s.parse::<Url>()?,
-            ..Default::default()
-        })
-    }
+    fn try_from(s: String) -> Result<Self, Self::Error> { Ok(s.parse::<Url>()?.into()) }
 }
 impl TryFrom<&String> for Page {
     type Error = ParseError;
 
-    fn try_from(s: &String) -> Result<Self, Self::Error> {
-        Ok(Self {
-            url: s.parse::<Url>()?,
-            ..Default::default()
-        })
-    }
+    fn try_from(s: &String) -> Result<Self, Self::Error> { Ok(s.parse::<Url>()?.into()) }
 }
 impl TryFrom<&mut String> for Page {
     type Error = ParseError;
 
-    fn try_from(s: &mut String) -> Result<Self, Self::Error> {
-        Ok(Self {
-            url: s.parse::<Url>()?,
-            ..Default::default()
-        })
-    }
+    fn try_from(s: &mut String) -> Result<Self, Self::Error> { Ok(s.parse::<Url>()?.into()) }
 }
 impl From<Url> for Page {
     fn from(url: Url) -> Self {
         Self {
-            url,
+            url: normalize(url),
             ..Default::default()
         }
     }
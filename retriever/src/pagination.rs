@@ -0,0 +1,71 @@
+use crate::{
+    error::Error,
+    extractor::{Pick, Rule},
+    page::Page,
+    selector::Selector,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// How a site splits the chapter list of an index over several pages.
+///
+/// ```toml
+/// [pagination]
+/// next = { select = "ul.pager a[rel=next]" }
+/// # or
+/// template = "?page={page}"
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pagination {
+    /// Link to the following page, read from its `href` unless `attr` says
+    /// otherwise
+    pub next: Option<Pick>,
+    /// Url of a page, relative to the index, `{page}` is replaced by the
+    /// page number. Used when `next` isn't set.
+    pub template: Option<String>,
+    /// Number of the first page after the index itself
+    pub start: u32,
+    /// Most pages read, the index included
+    pub max: usize,
+}
+
+impl Pagination {
+    /// Selector rule for `next`, checking that it parses.
+    pub fn rule(&self) -> Result<Option<Rule>, Error> {
+        let Some(pick) = &self.next else {
+            return Ok(None);
+        };
+        Ok(Some(Rule::Selector {
+            selector: pick.select.parse::<Selector>()?,
+            attr: Some(pick.attr.clone().unwrap_or_else(|| "href".to_owned())),
+            absolute: true,
+        }))
+    }
+
+    /// Page following `page`, the `n`th read counting the index as 0. With
+    /// a `next` selector `page` must still hold its html.
+    pub fn after(&self, index: &Url, page: &Page, n: u32) -> Option<Url> {
+        match (&self.next, &self.template) {
+            (Some(_), _) => self.rule().ok()??.string(page)?.parse().ok(),
+            (None, Some(_)) => self.page_url(index, self.start + n),
+            (None, None) => None,
+        }
+    }
+
+    /// Url of page `number` by the template.
+    pub fn page_url(&self, index: &Url, number: u32) -> Option<Url> {
+        let template = self.template.as_ref()?;
+        index.join(&template.replace("{page}", &number.to_string())).ok()
+    }
+}
+impl Default for Pagination {
+    fn default() -> Self {
+        Self {
+            next: None,
+            template: None,
+            start: 2,
+            max: 100,
+        }
+    }
+}
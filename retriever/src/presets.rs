@@ -1,15 +1,21 @@
 use crate::{
     page::{ContentType, Page},
+    series::{SeriesInfo, Status},
     Images,
     Index,
     Links,
     Next,
+    Series,
     Text,
     Title,
 };
 #[allow(unused_imports)]
 use log::debug;
-use select::predicate::{And, Any, Attr, Child, Descendant, Name, Or, Text as Txt};
+use select::{
+    document::Document,
+    node::Node,
+    predicate::{And, Any, Attr, Child, Descendant, Name, Or, Text as Txt},
+};
 
 pub fn default_title(page: &Page) -> Title {
     page.doc().map(|d| {
@@ -69,7 +75,7 @@ pub fn default_links(page: &Page) -> Links {
             if Page::try_from(&p).is_ok() {
                 p
             } else {
-                page.origin() + &p
+                page.origin() + p.as_str()
             }
         })
         .collect()
@@ -144,6 +150,102 @@ pub fn default_images(page: &Page) -> Images {
         )
     })
 }
+pub fn default_series(page: &Page) -> Series {
+    page.doc().map(|d| {
+        let meta = |p: &str| {
+            d.find(And(Name("meta"), Attr("property", p)))
+                .find_map(|m| m.attr("content"))
+                .map(|c| c.trim().to_owned())
+                .filter(|c| !c.is_empty())
+        };
+        SeriesInfo {
+            title: meta("og:title").or_else(|| default_title(page)),
+            url: Some(page.url.to_string()),
+            authors: labelled(&d, &["author"]),
+            artists: labelled(&d, &["artist", "illustrator"]),
+            alt_titles: labelled(&d, &["alternative", "alt name", "also known"]),
+            genres: labelled(&d, &["genre", "tags"]),
+            description: meta("og:description").or_else(|| {
+                d.find(Any)
+                    .filter(|n| {
+                        n.attr("class").or(n.attr("id")).is_some_and(|c| {
+                            let c = c.to_lowercase();
+                            ["summary", "description", "synopsis"]
+                                .iter()
+                                .any(|k| c.contains(k))
+                        })
+                    })
+                    .map(|n| n.text().trim().to_owned())
+                    .max_by_key(|t| t.len())
+                    .filter(|t| !t.is_empty())
+            }),
+            status: labelled(&d, &["status"])
+                .first()
+                .map(|s| Status::from(s.as_str()))
+                .unwrap_or_default(),
+            cover: meta("og:image").map(|c| {
+                page.url
+                    .join(&c)
+                    .map(|u| u.to_string())
+                    .unwrap_or(c)
+            }),
+            language: d
+                .find(Name("html"))
+                .find_map(|h| h.attr("lang"))
+                .map(str::to_owned)
+                .or_else(|| meta("og:locale")),
+        }
+    })
+}
+/// Values next to a "Label:" in the page, e.g. `<td>Author(s) :</td><td><a>..</a></td>`
+/// or `<li><b>Genres:</b> Action, Drama</li>`.
+fn labelled(d: &Document, labels: &[&str]) -> Vec<String> {
+    fn split(s: &str) -> Vec<String> {
+        s.split([',', ';', '|'])
+            .map(|v| v.trim().to_owned())
+            .filter(|v| !v.is_empty())
+            .collect()
+    }
+    fn next_element(n: Node) -> Option<Node> {
+        let mut sibling = n.next();
+        while let Some(s) = sibling {
+            if s.name().is_some() {
+                return Some(s);
+            }
+            sibling = s.next();
+        }
+        None
+    }
+    d.find(Txt)
+        .filter(|t| {
+            let text = t.text().trim().to_lowercase();
+            text.len() < 40 && labels.iter().any(|l| text.starts_with(l))
+        })
+        .find_map(|t| {
+            let label = t.text();
+            if let Some((_, v)) = label.split_once(':').filter(|(_, v)| !v.trim().is_empty()) {
+                return Some(split(v));
+            }
+            let parent = t.parent()?;
+            let holder = next_element(parent)
+                .or_else(|| next_element(t))
+                .or_else(|| parent.parent())?;
+            let mut links = holder
+                .find(Name("a"))
+                .map(|a| a.text().trim().to_owned())
+                .filter(|a| !a.is_empty())
+                .collect::<Vec<_>>();
+            if holder.name() == Some("a") {
+                links.insert(0, holder.text().trim().to_owned());
+            }
+            if !links.is_empty() {
+                return Some(links);
+            }
+            let text = holder.text().replace(label.as_str(), "");
+            Some(split(text.trim().trim_start_matches(':'))).filter(|v| !v.is_empty())
+        })
+        .unwrap_or_default()
+}
 
 pub fn realm_next(page: &Page) -> Next {
     page.html.as_ref().and_then(|d| {
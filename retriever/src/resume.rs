@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+use time::OffsetDateTime;
+use tokio::{fs::write, io};
+
+/// Suffix of files still being written, renamed away once complete.
+pub const PART: &str = ".part";
+
+/// Progress of an interrupted download, kept in the output directory so the
+/// next run for the same url skips what was already saved.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Resume {
+    /// Url the run was started with
+    pub url: String,
+    /// Pages saved completely
+    pub done: BTreeSet<String>,
+    /// Pages found but not saved yet
+    pub pending: Vec<String>,
+    /// Unix time of the interruption
+    pub interrupted_at: i64,
+}
+
+impl Resume {
+    pub const FILE: &'static str = "resume.json";
+
+    pub fn new<T: Into<String>>(url: T) -> Self {
+        Self {
+            url: url.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Writes `resume.json` into `dir`, stamped with the current time.
+    pub async fn save(&mut self, dir: &Path) -> io::Result<()> {
+        self.interrupted_at = OffsetDateTime::now_utc().unix_timestamp();
+        let json = serde_json::to_vec_pretty(self)?;
+        write(dir.join(Self::FILE), json).await
+    }
+
+    pub async fn load(dir: &Path) -> io::Result<Self> {
+        let json = tokio::fs::read(dir.join(Self::FILE)).await?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+
+    /// Rewrites every stored url with `f`, e.g. after the site moved hosts.
+    pub fn rewrite<F: Fn(&str) -> String>(&mut self, f: F) {
+        self.url = f(&self.url);
+        self.done = self.done.iter().map(|u| f(u)).collect();
+        self.pending = self.pending.iter().map(|u| f(u)).collect();
+    }
+
+    /// Removes the state of a run that finished.
+    pub async fn clear(dir: &Path) -> io::Result<()> {
+        match tokio::fs::remove_file(dir.join(Self::FILE)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Deletes the `.part` files interrupted writes of `paths` left next to
+/// them, returns how many were removed. Anything but a plain file is kept.
+pub fn remove_partials<'a, I: IntoIterator<Item = &'a Path>>(paths: I) -> io::Result<usize> {
+    let mut count = 0;
+    for path in paths {
+        let mut part = path.as_os_str().to_owned();
+        part.push(PART);
+        let part = PathBuf::from(part);
+        if fs::symlink_metadata(&part).is_ok_and(|m| m.file_type().is_file()) {
+            fs::remove_file(&part)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
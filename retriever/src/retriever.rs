@@ -1,16 +1,29 @@
 use crate::{
+    bandwidth::Bandwidth,
+    canonical::{normalize_str, normalize_with, page_key},
+    client::{ClientConfig, CookieJar},
+    error::Error,
     extractor::{Extractor, Manifest},
+    hooks::Hooks,
+    order::{reading_order, ListOrder},
     page::{ContentType, Page},
+    pagination::Pagination,
+    robots::Robots,
+    search::SearchResult,
+    series::SeriesInfo,
+    stats::{HostStats, Stats},
 };
 use core::fmt::Debug;
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use futures::future::join_all;
 #[allow(unused_imports)]
-use log::{debug, info, trace};
-use reqwest::{redirect::Policy, Client};
-use std::time::Duration;
-use tokio::time::sleep;
-use url::Host;
+use log::{debug, info, trace, warn};
+use reqwest::Client;
+use select::document::Document;
+use std::{collections::HashSet, fs, path::{Path, PathBuf}, sync::Arc, time::Duration};
+use tokio::time::{sleep, sleep_until, Instant};
+use tokio_util::sync::CancellationToken;
+use url::{Host, Url};
 
 pub type TitleType = String;
 pub type NextType = Option<Page>;
@@ -27,6 +40,19 @@ pub struct Retriever {
     extr: Vec<Extractor>,
     default_extractor: usize,
     client: Client,
+    jar: Arc<CookieJar>,
+    jar_file: Option<PathBuf>,
+    robots: Option<DashMap<String, Robots>>,
+    limits: DashMap<String, (Duration, Instant)>,
+    sessions: DashSet<String>,
+    /// Hosts that moved, to the host and port they are at now
+    moved: DashMap<String, (String, Option<u16>)>,
+    stats: Stats,
+    cancel: CancellationToken,
+    bandwidth: Option<Bandwidth>,
+    hooks: Hooks,
+    /// Query parameters dropped from chapter urls besides the default ones
+    drop_params: Vec<String>,
 }
 
 #[allow(unused_variables)]
@@ -42,33 +68,466 @@ impl Retriever {
         }
     }
 
+    pub fn with_config(config: &ClientConfig) -> Result<Self, Error> {
+        let (client, jar) = config.build()?;
+        let m: Manifest = {
+            let mut m = Manifest::default();
+            m.rename("Default");
+            m
+        };
+        Ok(Self {
+            extractors: DashMap::new(),
+            targets: DashMap::new(),
+            manifests: vec![m],
+            default_extractor: 0,
+            client,
+            jar,
+            jar_file: config.get_persist().cloned(),
+            robots: None,
+            limits: DashMap::new(),
+            sessions: DashSet::new(),
+            moved: DashMap::new(),
+            stats: Stats::default(),
+            cancel: CancellationToken::new(),
+            bandwidth: None,
+            hooks: Hooks::default(),
+            drop_params: vec![],
+            extr: vec![Default::default()],
+        })
+    }
+
+    /// Registers a site definition for its hosts and mirrors, returns its index.
+    pub fn add_manifest(&mut self, manifest: Manifest) -> Result<usize, Error> {
+        let extractor = manifest.extractor()?;
+        let idx = self.manifests.len();
+        for host in manifest.hosts().iter().chain(manifest.mirrors()) {
+            match Host::parse(host) {
+                Ok(h) => {
+                    self.extractors.insert(h, idx);
+                }
+                Err(e) => warn!("{}: bad host {}: {}", manifest.name(), host, e),
+            }
+        }
+        self.manifests.push(manifest);
+        self.extr.push(extractor);
+        Ok(idx)
+    }
+
+    /// Loads every `*.toml` site definition in `dir`.
+    pub fn load_manifests(&mut self, dir: &Path) -> Result<usize, Error> {
+        let mut count = 0;
+        for entry in fs::read_dir(dir)?.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|e| e != "toml") {
+                continue;
+            }
+            let manifest: Manifest = fs::read_to_string(&path)?
+                .parse()
+                .map_err(|e| Error::Manifest(format!("{:?}: {}", path, e)))?;
+            debug!("Loaded site definition {} from {:?}", manifest.name(), path);
+            self.add_manifest(manifest)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Site definition by name.
+    pub fn site(&self, name: &str) -> Option<&Manifest> {
+        self.manifests.iter().find(|m| m.name() == name)
+    }
+
+    /// Looks for series called `query` using the site's search endpoint.
+    pub async fn search(&self, site: &str, query: &str) -> Result<Vec<SearchResult>, Error> {
+        let manifest = self
+            .site(site)
+            .ok_or_else(|| Error::Manifest(format!("unknown site: {}", site)))?;
+        let search = manifest
+            .search()
+            .ok_or_else(|| Error::Manifest(format!("{} has no search", site)))?;
+        let url = search.url_for(query)?;
+        let page = Page::from(url.clone());
+        self.check_robots(&page).await?;
+        if manifest.login().is_some() && !self.sessions.contains(manifest.name()) {
+            self.login(manifest).await?;
+        }
+        self.throttle(&page).await;
+        let html = self
+            .client
+            .get(url.clone())
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let results = search.results(&html, &url)?;
+        debug!("{} results for {:?} on {}", results.len(), query, site);
+        Ok(results)
+    }
+
+    /// Site definition registered for the url's host.
+    pub fn manifest(&self, url: &Url) -> Option<&Manifest> {
+        let idx = *self.extractors.get(&url.host()?.to_owned())?;
+        self.manifests.get(idx)
+    }
+
+    /// Extractor of the site definition registered for the url's host.
+    pub fn extractor_for(&self, url: &Url) -> &Extractor {
+        url.host()
+            .and_then(|h| self.extractors.get(&h.to_owned()).map(|i| *i))
+            .and_then(|i| self.extr.get(i))
+            .unwrap_or(&self.extr[self.default_extractor])
+    }
+
+    /// The url on the host its site moved to, if it did.
+    pub fn relocate(&self, url: &Url) -> Url {
+        let mut url = url.clone();
+        let to = url.host_str().and_then(|h| self.moved.get(h)).map(|t| t.value().clone());
+        if let Some((host, port)) = to {
+            if url.set_host(Some(&host)).is_err() || url.set_port(port).is_err() {
+                warn!("Can't move {} to {}", url, host);
+            }
+        }
+        url
+    }
+
+    /// Hosts that moved during this run, with where they are now.
+    pub fn moves(&self) -> Vec<(String, (String, Option<u16>))> {
+        self.moved
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect()
+    }
+
+    /// Remembers that the host of `from` now lives at the host of `to`.
+    fn record_move(&self, from: &Url, to: &Url) {
+        let (Some(old), Some(new)) = (from.host_str(), to.host_str()) else {
+            return;
+        };
+        if old == new && from.port() == to.port() {
+            return;
+        }
+        info!("{} moved to {}", old, new);
+        let now = (new.to_owned(), to.port());
+        for mut m in self.moved.iter_mut() {
+            if m.value().0 == old {
+                *m = now.clone();
+            }
+        }
+        self.moved.remove(new);
+        self.moved.insert(old.to_owned(), now);
+        let idx = from.host().and_then(|h| self.extractors.get(&h.to_owned()).map(|i| *i));
+        if let (Some(idx), Some(host)) = (idx, to.host()) {
+            self.extractors.entry(host.to_owned()).or_insert(idx);
+        }
+    }
+
+    /// The url on the first host or mirror of its site not in `tried`.
+    fn mirror_of(&self, url: &Url, tried: &[(String, Option<u16>)]) -> Option<Url> {
+        let manifest = self.manifest(url)?;
+        manifest
+            .hosts()
+            .iter()
+            .chain(manifest.mirrors())
+            .filter(|h| !tried.iter().any(|(t, _)| t == *h))
+            .find_map(|h| {
+                let mut url = url.clone();
+                url.set_host(Some(h)).ok()?;
+                Some(url)
+            })
+    }
+
+    /// Runs the site's login flow and remembers the session.
+    pub async fn login(&self, manifest: &Manifest) -> Result<(), Error> {
+        let Some(login) = manifest.login() else {
+            return Ok(());
+        };
+        info!("Logging in to {}", manifest.name());
+        let creds = login.credentials(manifest.name())?;
+        let url = login
+            .url
+            .parse::<Url>()
+            .map_err(|_| Error::Login(format!("bad login url: {}", login.url)))?;
+        let form_page = self.client.get(url.clone()).send().await?.text().await?;
+        let (target, form) = {
+            let doc = Document::from(form_page.as_str());
+            (login.target(&doc, &url)?, login.form(&doc, &creds))
+        };
+        trace!("posting login form to {}", target);
+        let res = self.client.post(target.clone()).form(&form).send().await?;
+        let status = res.status();
+        let body = res.text().await?;
+        let ok = status.is_success() &&
+            match (&login.success, &login.success_cookie) {
+                (Some(text), _) => body.contains(text.as_str()),
+                (None, Some(cookie)) => self.jar.has_cookie(&target, cookie),
+                (None, None) => !body.contains(&format!("name=\"{}\"", login.password_field)),
+            };
+        if !ok {
+            self.sessions.remove(manifest.name());
+            return Err(Error::Login(format!("{} ({})", manifest.name(), status)));
+        }
+        self.sessions.insert(manifest.name().to_owned());
+        Ok(())
+    }
+
+    /// Fetch each host's robots.txt and refuse the paths it disallows.
+    pub fn respect_robots(&mut self, respect: bool) -> &mut Self {
+        self.robots = respect.then(DashMap::new);
+        self
+    }
+
+    /// Minimum time between two requests to `host`. Never lowered below a
+    /// robots.txt `Crawl-delay`.
+    pub fn rate_limit(&self, host: &str, min: Duration) {
+        let mut entry = self
+            .limits
+            .entry(host.to_owned())
+            .or_insert((Duration::ZERO, Instant::now()));
+        entry.0 = entry.0.max(min);
+    }
+
+    /// Waits until the next request to the page's host is allowed.
+    async fn throttle(&self, page: &Page) {
+        let Some(host) = page.host() else {
+            return;
+        };
+        let at = match self.limits.get_mut(&host) {
+            Some(mut entry) => {
+                let (delay, next) = *entry;
+                let at = next.max(Instant::now());
+                entry.1 = at + delay;
+                at
+            }
+            None => return,
+        };
+        sleep_until(at).await;
+    }
+
+    async fn check_robots(&self, page: &Page) -> Result<(), Error> {
+        let (Some(cache), Some(host)) = (&self.robots, page.host()) else {
+            return Ok(());
+        };
+        let origin = page.origin();
+        if !cache.contains_key(&origin) {
+            let robots = match self.client.get(origin.clone() + "/robots.txt").send().await {
+                Ok(res) if res.status().is_success() => {
+                    Robots::parse(&res.text().await.unwrap_or_default(), "retriever")
+                }
+                Ok(res) => {
+                    debug!("No robots.txt for {} ({})", origin, res.status());
+                    Robots::allow_all()
+                }
+                Err(e) => {
+                    warn!("Failed to fetch robots.txt for {}: {}", origin, e);
+                    Robots::allow_all()
+                }
+            };
+            if let Some(delay) = robots.crawl_delay() {
+                info!("Crawl-delay for {}: {:?}", host, delay);
+                self.rate_limit(&host, delay);
+            }
+            cache.insert(origin.clone(), robots);
+        }
+        let path = match page.url.query() {
+            Some(q) => format!("{}?{}", page.path(), q),
+            None => page.path().to_owned(),
+        };
+        match cache.get(&origin) {
+            Some(robots) if !robots.allowed(&path) => Err(Error::Disallowed(page.url.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn client(&self) -> &Client { &self.client }
+
+    pub fn jar(&self) -> &Arc<CookieJar> { &self.jar }
+
+    /// Drops these query parameters from chapter urls on top of
+    /// `canonical::DEFAULT_DENYLIST`, `name*` matching a whole prefix.
+    pub fn drop_params<I: IntoIterator<Item = S>, S: Into<String>>(
+        &mut self, params: I,
+    ) -> &mut Self {
+        self.drop_params = params.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// `url` normalized, the parameters of `drop_params` dropped too.
+    pub(crate) fn normalize(&self, url: Url) -> Url { normalize_with(url, &self.drop_params) }
+
+    /// What the page `link` leads to is told apart by, see `page_key`.
+    fn link_key(&self, link: &str) -> String { page_key(&normalize_str(link, &self.drop_params)) }
+
+    /// Caps the bytes per second of all page and image downloads together.
+    pub fn limit_bandwidth(&mut self, bytes_per_sec: Option<u64>) -> &mut Self {
+        self.bandwidth = bytes_per_sec.map(Bandwidth::new);
+        self
+    }
+
+    /// Commands run on download events.
+    pub fn set_hooks(&mut self, hooks: Hooks) -> &mut Self {
+        self.hooks = hooks;
+        self
+    }
+
+    pub fn hooks(&self) -> &Hooks { &self.hooks }
+
+    /// Token that stops new requests once cancelled, in-flight ones finish.
+    pub fn cancellation(&self) -> &CancellationToken { &self.cancel }
+
+    pub fn is_cancelled(&self) -> bool { self.cancel.is_cancelled() }
+
+    /// Request counters of every host visited so far.
+    pub fn stats(&self) -> Vec<HostStats> { self.stats.snapshot() }
+
+    /// Writes the cookie jar back to the file it was loaded from, if any.
+    pub fn save_cookies(&self) -> Result<(), Error> {
+        match &self.jar_file {
+            Some(path) => self.jar.save(path),
+            None => Ok(()),
+        }
+    }
+
     pub async fn fetch_all(
         &self, pages: &mut [Page], extractor: &Extractor, visual: bool, delay: u64,
     ) {
         join_all(pages.chunks_mut(10).map(|p| async {
             sleep(Duration::from_millis(delay)).await;
             for i in p {
-                self.fetch(i, extractor, visual).await;
+                if self.cancel.is_cancelled() {
+                    break;
+                }
+                if let Err(e) = self.fetch(i, extractor, visual).await {
+                    warn!("{}", e);
+                }
                 sleep(Duration::from_millis(20)).await;
             }
         }))
         .await;
     }
 
-    pub async fn fetch(&self, page: &mut Page, extractor: &Extractor, visual: bool) {
+    pub async fn fetch(
+        &self, page: &mut Page, extractor: &Extractor, visual: bool,
+    ) -> Result<(), Error> {
         if let Some(time) = page.last {
             if time.minute() < 1 {
                 info!("Visited recently");
-                return;
+                self.stats.cache_hit(&page.host().unwrap_or_default());
+                return Ok(());
+            }
+        }
+        let res = self.visit(page, extractor, visual).await;
+        page.empty();
+        res
+    }
+
+    /// Downloads the page's html without extracting anything from it.
+    pub async fn fetch_html(&self, page: &mut Page) -> Result<String, Error> {
+        self.visit(page, &Extractor::new(), false).await?;
+        page.html
+            .take()
+            .ok_or_else(|| Error::NoContent(page.url.clone()))
+    }
+
+    /// Visits the page on the host its site lives on now. When the host is
+    /// unreachable the site's other hosts and mirrors are tried in turn for
+    /// this request only, while a permanent redirect to another host is
+    /// followed and remembered for later pages.
+    async fn visit(
+        &self, page: &mut Page, extractor: &Extractor, visual: bool,
+    ) -> Result<(), Error> {
+        page.url = self.relocate(&page.url);
+        let mut home = place(&page.url);
+        let mut tried = vec![home.clone()];
+        loop {
+            let err = match self.visit_host(page, extractor, visual).await {
+                Ok(()) if place(&page.url) == home => return Ok(()),
+                Ok(()) => {
+                    // Served by a mirror, the page keeps the url of its site
+                    let (host, port) = home;
+                    if page.url.set_host(Some(&host)).is_err() || page.url.set_port(port).is_err() {
+                        warn!("Can't move {} back to {}", page.url, host);
+                    }
+                    return Ok(());
+                }
+                Err(e) => e,
+            };
+            let to = match &err {
+                Error::Moved(to) => Some(self.normalize(to.clone())),
+                Error::Http(e) if e.is_connect() || e.is_timeout() => {
+                    self.mirror_of(&page.url, &tried)
+                }
+                _ => None,
+            };
+            let Some(to) = to.filter(|u| !tried.contains(&place(u))) else {
+                return Err(err);
+            };
+            warn!("{}: {}, trying {}", page.url, err, to);
+            if matches!(err, Error::Moved(_)) {
+                self.record_move(&page.url, &to);
+                home = place(&to);
             }
+            tried.push(place(&to));
+            page.url = to;
         }
-        page.visit(self.client.clone(), extractor, visual).await;
     }
 
-    pub async fn fetch_index<'a: 'b, 'b>(
+    /// Visits the page observing robots.txt, rate limits and the site's
+    /// login, leaving its html in place.
+    async fn visit_host(
+        &self, page: &mut Page, extractor: &Extractor, visual: bool,
+    ) -> Result<(), Error> {
+        self.check_robots(page).await?;
+        let site = self.manifest(&page.url).filter(|m| m.login().is_some());
+        if let Some(m) = site {
+            if !self.sessions.contains(m.name()) {
+                self.login(m).await?;
+            }
+        }
+        self.throttle(page).await;
+        if self.cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        // Page::visit leaves the page untouched when cancelled
+        let last = page.last;
+        page.visit(
+            self.client.clone(),
+            extractor,
+            visual,
+            &self.stats,
+            &self.cancel,
+            self.bandwidth.as_ref(),
+        )
+        .await?;
+        if page.last == last {
+            return Err(Error::Cancelled);
+        }
+        if let Some((m, login)) = site.and_then(|m| Some((m, m.login()?))) {
+            if login.expired(page) {
+                info!("Session expired for {}", m.name());
+                self.login(m).await?;
+                self.throttle(page).await;
+                page.visit(
+                    self.client.clone(),
+                    extractor,
+                    visual,
+                    &self.stats,
+                    &self.cancel,
+                    self.bandwidth.as_ref(),
+                )
+                .await?;
+                if login.expired(page) {
+                    return Err(Error::Login(format!("{}: session rejected", m.name())));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces `page` with the series index it links to.
+    pub async fn fetch_index<'a>(
         &self, page: &'a mut Page, kind: bool,
-    ) -> Result<&'b mut Page, &'a mut Page> {
-        self.check_page(page, kind).await;
+    ) -> Result<&'a mut Page, Error> {
+        self.check_page(page, kind).await?;
         if let Some(mut p) = page
             .content
             .index()
@@ -79,13 +538,16 @@ impl Retriever {
             *page = p;
             return Ok(page);
         }
-        Err(page)
+        Err(Error::NoContent(page.url.clone()))
     }
 
     pub async fn fetch_next<'a: 'b, 'b>(
         &self, page: &'a mut Page, kind: bool,
     ) -> Result<&'b mut Page, &'a mut Page> {
-        self.check_page(page, kind).await;
+        if let Err(e) = self.check_page(page, kind).await {
+            warn!("{}", e);
+            return Err(page);
+        }
         if let Some(mut next) = page.next() {
             next.next_by = page.next_by;
             *page = next;
@@ -94,25 +556,104 @@ impl Retriever {
         Err(page)
     }
 
-    pub async fn fetch_links<'a: 'b, 'b>(
+    /// Chapter links of an index in reading order, from all its pages when
+    /// the site paginates the list.
+    pub async fn fetch_links<'a>(
         &self, page: &'a mut Page, kind: bool,
-    ) -> Result<&'b mut Page, &'a mut Page> {
-        self.check_page(page, kind).await;
-        let cnt = page
-            .content
-            .links()
-            .as_ref()
-            .map(|v| ContentType::Chapters(v.to_owned())); // FIXME: don't clone
+    ) -> Result<&'a mut Page, Error> {
+        let pagination = self.manifest(&page.url).and_then(Manifest::pagination);
+        // Visited directly to keep the html the next page link is read from
+        let res = match pagination {
+            Some(_) if page.last.is_none() => {
+                self.visit(page, self.extractor_for(&page.url), kind).await
+            }
+            _ => self.check_page(page, kind).await,
+        };
+        res?;
+        let mut links = page.content.links().clone();
+        if let (Some(p), Some(found)) = (pagination, links.as_mut()) {
+            found.extend(self.more_links(page, p, kind).await);
+        }
+        page.empty();
+        let order = self.manifest(&page.url).map_or(ListOrder::Auto, Manifest::order);
+        let cnt = links.map(|v| {
+            let mut seen = HashSet::new();
+            let links = v
+                .iter()
+                .map(|l| normalize_str(l, &self.drop_params))
+                .filter(|l| seen.insert(page_key(l)))
+                .collect();
+            ContentType::Chapters(reading_order(links, order))
+        });
         if cnt.is_some() {
             trace!("{:?}", cnt);
             page.content.data = cnt;
             return Ok(page);
         }
-        Err(page)
+        Err(Error::NoContent(page.url.clone()))
+    }
+
+    /// Chapter links on the pages following `index`, in order. Stops at the
+    /// first page that adds nothing new.
+    async fn more_links(
+        &self, index: &mut Page, pagination: &Pagination, kind: bool,
+    ) -> Vec<String> {
+        if index.html.is_none() && pagination.next.is_some() {
+            match self.fetch_html(&mut Page::from(index.url.clone())).await {
+                Ok(html) => index.html = Some(html),
+                Err(e) => {
+                    warn!("{}", e);
+                    return vec![];
+                }
+            }
+        }
+        // Only the links are wanted, presets may not cope with a bare list
+        let mut extractor = self.extractor_for(&index.url).clone();
+        extractor.set_title(None);
+        extractor.set_next(None);
+        extractor.set_index(None);
+        extractor.set_text(None);
+        extractor.set_images(None);
+        extractor.set_series(None);
+        let mut seen = index
+            .content
+            .links()
+            .iter()
+            .flatten()
+            .map(|l| self.link_key(l))
+            .collect::<HashSet<_>>();
+        let mut visited = HashSet::from([index.url.clone()]);
+        let mut links = vec![];
+        let mut next = pagination.after(&index.url, index, 0);
+        let mut n = 0;
+        while let Some(url) = next.take() {
+            n += 1;
+            if n >= pagination.max || !visited.insert(url.clone()) || self.is_cancelled() {
+                break;
+            }
+            let mut more = Page::from(url);
+            if let Err(e) = self.visit(&mut more, &extractor, kind).await {
+                warn!("{}", e);
+                break;
+            }
+            let before = links.len();
+            let found = more.content.links().clone().unwrap_or_default();
+            links.extend(found.into_iter().filter(|l| seen.insert(self.link_key(l))));
+            if links.len() == before {
+                debug!("{} adds no chapters", more.url);
+                break;
+            }
+            next = pagination.after(&index.url, &more, n as u32);
+        }
+        debug!("{} more chapters on {} pages after {}", links.len(), n, index.url);
+        links
     }
 
     pub async fn fetch_content(&self, page: &mut Page, kind: bool) -> Option<Vec<Page>> {
-        self.check_page(page, kind).await;
+        if let Err(e) = self.check_page(page, kind).await {
+            warn!("{}", e);
+            return None;
+        }
         let out = page.content.data.as_ref().and_then(|p| {
             trace!("to_pages: {:?}", &p.to_pages());
             p.to_pages()
@@ -120,18 +661,32 @@ impl Retriever {
         out
     }
 
-    pub async fn check_page(&self, page: &mut Page, kind: bool) {
-        if page.last.is_none() {
-            self.fetch(page, &self.extr[self.default_extractor], kind)
-                .await;
+    /// Series metadata of an index page.
+    pub async fn fetch_series(&self, page: &mut Page, kind: bool) -> Option<SeriesInfo> {
+        if let Err(e) = self.check_page(page, kind).await {
+            warn!("{}", e);
+            return None;
+        }
+        page.content.series().cloned()
+    }
+
+    pub async fn check_page(&self, page: &mut Page, kind: bool) -> Result<(), Error> {
+        if page.last.is_some() {
+            self.stats.cache_hit(&page.host().unwrap_or_default());
+            return Ok(());
         }
+        let extractor = self.extractor_for(&page.url);
+        self.fetch(page, extractor, kind).await
     }
 
     //TODO: extract all data from a Page at the same time
 
     pub fn add_extractor(&mut self, from: Option<usize>) -> bool {
-        if let Some(r) = self.manifests.get(from.unwrap_or(0)) {
-            self.manifests.insert(self.manifests.len(), r.clone());
+        let from = from.unwrap_or(0);
+        if let (Some(r), Some(e)) = (self.manifests.get(from), self.extr.get(from)) {
+            let (r, e) = (r.clone(), e.clone());
+            self.manifests.push(r);
+            self.extr.push(e);
             return true;
         }
         false
@@ -143,28 +698,11 @@ impl Retriever {
 
 impl Default for Retriever {
     fn default() -> Self {
-        let extractors = DashMap::new();
-        let targets = DashMap::new();
-        let m: Manifest = {
-            let mut m = Manifest::default();
-            m.rename("Default");
-            m
-        };
-        let manifests: Vec<Manifest> = vec![m];
-        let client = Client::builder()
-            .connection_verbose(true)
-            .cookie_store(true)
-            .http2_adaptive_window(true)
-            .redirect(Policy::default())
-            .build()
-            .unwrap();
-        Self {
-            extractors,
-            targets,
-            manifests,
-            default_extractor: 0,
-            client,
-            extr: vec![Default::default()],
-        }
+        Self::with_config(&ClientConfig::default()).expect("Failed to build the default client")
     }
 }
+
+/// Host and port a url is served from.
+fn place(url: &Url) -> (String, Option<u16>) {
+    (url.host_str().unwrap_or_default().to_owned(), url.port())
+}
@@ -0,0 +1,113 @@
+#[allow(unused_imports)]
+use log::{debug, info, trace};
+use std::time::Duration;
+
+/// Rules from a host's `robots.txt` that apply to our user agent.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Robots {
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    /// Robots that allow everything, used when a host has no `robots.txt`.
+    pub fn allow_all() -> Self { Self::default() }
+
+    /// Keeps the group naming `agent` if there is one, the `*` group otherwise.
+    /// Groups name agents by product token, compared whole ignoring case and
+    /// any `/version`.
+    pub fn parse(src: &str, agent: &str) -> Self {
+        fn token(agent: &str) -> &str { agent.split('/').next().unwrap_or_default().trim() }
+        let agent = token(agent).to_ascii_lowercase();
+        let mut specific: Option<Robots> = None;
+        let mut wildcard: Option<Robots> = None;
+        let mut group: Vec<String> = vec![];
+        let mut current = Robots::default();
+        let mut in_rules = false;
+        let mut flush = |group: &mut Vec<String>, current: &mut Robots| {
+            let robots = std::mem::take(current);
+            for a in group.drain(..) {
+                if a == "*" {
+                    wildcard.get_or_insert_with(Default::default).merge(&robots);
+                } else if token(&a) == agent {
+                    specific.get_or_insert_with(Default::default).merge(&robots);
+                }
+            }
+        };
+        for line in src.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        flush(&mut group, &mut current);
+                        in_rules = false;
+                    }
+                    group.push(value.to_ascii_lowercase());
+                }
+                "allow" | "disallow" if !value.is_empty() => {
+                    in_rules = true;
+                    current
+                        .rules
+                        .push((key.trim().eq_ignore_ascii_case("allow"), value.to_owned()));
+                }
+                "disallow" => in_rules = true,
+                "crawl-delay" => {
+                    in_rules = true;
+                    current.crawl_delay = value.parse::<f64>().ok().map(Duration::from_secs_f64);
+                }
+                _ => (),
+            }
+        }
+        flush(&mut group, &mut current);
+        let robots = specific.or(wildcard).unwrap_or_default();
+        trace!("robots for {}: {:?}", agent, robots);
+        robots
+    }
+
+    /// Longest matching rule decides, `Allow` wins a tie.
+    pub fn allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| matches(pattern, path))
+            .max_by(|(a, p), (b, q)| p.len().cmp(&q.len()).then(a.cmp(b)))
+            .map(|(allow, _)| *allow)
+            .unwrap_or(true)
+    }
+
+    pub fn crawl_delay(&self) -> Option<Duration> { self.crawl_delay }
+
+    fn merge(&mut self, other: &Robots) {
+        self.rules.extend(other.rules.iter().cloned());
+        self.crawl_delay = self.crawl_delay.max(other.crawl_delay);
+    }
+}
+
+/// robots.txt path matching: a prefix match where `*` matches any run of
+/// characters and a trailing `$` anchors the end.
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    for (i, part) in parts.iter().enumerate() {
+        let last = i + 1 == parts.len();
+        if last && anchored {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
@@ -0,0 +1,145 @@
+use crate::{error::Error, page::Page, selector::Selector};
+#[allow(unused_imports)]
+use log::{debug, info, trace, warn};
+use rhai::{Array, Dynamic, Engine, Scope, AST};
+use select::document::Document;
+use std::{
+    fmt::{self, Debug},
+    sync::{Arc, OnceLock},
+};
+use url::Url;
+
+/// A sandboxed Rhai script backing an extractor field.
+///
+/// The script sees the page as `html` and `url` and returns a string or an
+/// array of strings. Besides the Rhai standard library it can call
+/// `select(html, selector)` for the text of matching elements,
+/// `select_attr(html, selector, attr)` for an attribute of them and
+/// `join_url(base, href)` to resolve relative links.
+///
+/// ```toml
+/// [scripts]
+/// images = '''
+/// let start = html.index_of("var images = [") + 14;
+/// html.sub_string(start).split("]")[0].split(",").map(|s| {
+///     s.replace("\"", "");
+///     join_url(url, s)
+/// })
+/// '''
+/// ```
+#[derive(Clone)]
+pub struct Script {
+    source: String,
+    ast: Arc<AST>,
+}
+
+impl Script {
+    pub fn new<T: Into<String>>(source: T) -> Result<Self, Error> {
+        let source = source.into();
+        let ast = engine()
+            .compile(&source)
+            .map_err(|e| Error::Script(e.to_string()))?;
+        Ok(Self {
+            source,
+            ast: Arc::new(ast),
+        })
+    }
+
+    pub fn source(&self) -> &str { &self.source }
+
+    fn run(&self, page: &Page) -> Option<Dynamic> {
+        let mut scope = Scope::new();
+        scope.push("html", page.html.clone().unwrap_or_default());
+        scope.push("url", page.url.to_string());
+        match engine().eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast) {
+            Ok(out) if out.is_unit() => None,
+            Ok(out) => Some(out),
+            Err(e) => {
+                warn!("Script failed on {}: {}", page.url, e);
+                None
+            }
+        }
+    }
+
+    /// Runs the script for a single value.
+    pub fn string(&self, page: &Page) -> Option<String> {
+        let out = self.run(page)?;
+        if out.is_array() {
+            return out.into_array().ok()?.into_iter().next().map(|d| d.to_string());
+        }
+        Some(out.to_string())
+    }
+
+    /// Runs the script for a list of values.
+    pub fn list(&self, page: &Page) -> Option<Vec<String>> {
+        let out = self.run(page)?;
+        if out.is_array() {
+            return Some(
+                out.into_array()
+                    .ok()?
+                    .into_iter()
+                    .map(|d| d.to_string())
+                    .collect(),
+            );
+        }
+        Some(vec![out.to_string()])
+    }
+}
+impl Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Script")
+            .field("source", &format!("{} bytes", self.source.len()))
+            .finish()
+    }
+}
+
+/// Shared engine, limited so a broken script can't hang or exhaust a crawl.
+fn engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(5_000_000)
+            .set_max_call_levels(32)
+            .set_max_string_size(32 << 20)
+            .set_max_array_size(100_000)
+            .set_max_map_size(10_000)
+            .disable_symbol("eval");
+        engine.on_print(|s| debug!("script: {}", s));
+        engine.register_fn("select", |html: &str, selector: &str| -> Array {
+            nodes(html, selector, |n| Some(n.text()))
+        });
+        engine.register_fn(
+            "select_attr",
+            |html: &str, selector: &str, attr: &str| -> Array {
+                nodes(html, selector, |n| n.attr(attr).map(str::to_owned))
+            },
+        );
+        engine.register_fn("join_url", |base: &str, href: &str| -> String {
+            Url::parse(base)
+                .and_then(|b| b.join(href))
+                .map(|u| u.to_string())
+                .unwrap_or_else(|_| href.to_owned())
+        });
+        engine
+    })
+}
+
+fn nodes(
+    html: &str, selector: &str, f: impl Fn(select::node::Node) -> Option<String>,
+) -> Array {
+    let selector = match selector.parse::<Selector>() {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("{}", e);
+            return Array::new();
+        }
+    };
+    let doc = Document::from(html);
+    selector
+        .find(&doc)
+        .into_iter()
+        .filter_map(f)
+        .map(Dynamic::from)
+        .collect()
+}
@@ -0,0 +1,74 @@
+use crate::{error::Error, selector::Selector};
+use reqwest::Url;
+use select::{document::Document, predicate::Name};
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded::byte_serialize;
+
+/// Search endpoint of a site definition.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Search {
+    /// Search page, `{query}` is replaced by the url encoded query
+    pub url: String,
+    /// Selector matching one element per result
+    pub results: String,
+    /// Selector for the title inside a result, defaults to the link's text
+    pub title: Option<String>,
+    /// Selector for the link inside a result, defaults to the first `a`
+    pub link: Option<String>,
+    /// Selector for the cover inside a result, defaults to the first `img`
+    pub cover: Option<String>,
+}
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub cover: Option<String>,
+}
+
+impl Search {
+    pub fn url_for(&self, query: &str) -> Result<Url, Error> {
+        let query = byte_serialize(query.as_bytes()).collect::<String>();
+        self.url
+            .replace("{query}", &query)
+            .parse()
+            .map_err(|_| Error::Manifest(format!("bad search url: {}", self.url)))
+    }
+
+    /// Candidates found on a result page fetched from `base`.
+    pub fn results(&self, html: &str, base: &Url) -> Result<Vec<SearchResult>, Error> {
+        let parse = |s: &Option<String>| s.as_deref().map(str::parse::<Selector>).transpose();
+        let (title, link, cover) = (parse(&self.title)?, parse(&self.link)?, parse(&self.cover)?);
+        let doc = Document::from(html);
+        Ok(self
+            .results
+            .parse::<Selector>()?
+            .find(&doc)
+            .into_iter()
+            .filter_map(|result| {
+                let a = match &link {
+                    Some(s) => s.find_in(result).into_iter().next(),
+                    None if result.name() == Some("a") => Some(result),
+                    None => result.find(Name("a")).next(),
+                }?;
+                let url = base.join(a.attr("href")?).ok()?.to_string();
+                let title = match &title {
+                    Some(s) => s.find_in(result).into_iter().next()?.text(),
+                    None => a.attr("title").map(str::to_owned).unwrap_or_else(|| a.text()),
+                };
+                let cover = match &cover {
+                    Some(s) => s.find_in(result).into_iter().next(),
+                    None => result.find(Name("img")).next(),
+                }
+                .and_then(|i| i.attr("data-src").or_else(|| i.attr("src")))
+                .and_then(|src| base.join(src).ok())
+                .map(|u| u.to_string());
+                Some(SearchResult {
+                    title: title.trim().to_owned(),
+                    url,
+                    cover,
+                })
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,244 @@
+use crate::error::Error;
+use select::{document::Document, node::Node, predicate::Predicate};
+use std::{
+    fmt,
+    iter::Peekable,
+    str::{Chars, FromStr},
+};
+
+/// The subset of CSS selectors site definitions use: type, `.class`, `#id`,
+/// `[attr]`, `[attr=value]` (also `~=`, `^=`, `$=`, `*=`), descendant and
+/// `>` child combinators, and `,` lists.
+///
+/// ```
+/// # use retriever::selector::Selector;
+/// let s: Selector = "div.chapters > a[href^=\"/read\"], ul#list a".parse().unwrap();
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Selector {
+    source: String,
+    alternatives: Vec<Vec<(Combinator, Compound)>>,
+}
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+struct Compound {
+    name: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    attrs: Vec<(String, Option<(char, String)>)>,
+}
+
+impl Selector {
+    /// Matching nodes in document order.
+    pub fn find<'a>(&self, doc: &'a Document) -> Vec<Node<'a>> { doc.find(self).collect() }
+
+    /// Matching descendants of `node` in document order.
+    pub fn find_in<'a>(&self, node: Node<'a>) -> Vec<Node<'a>> { node.find(self).collect() }
+
+    pub fn as_str(&self) -> &str { &self.source }
+}
+impl Predicate for &Selector {
+    fn matches(&self, node: &Node) -> bool {
+        node.name().is_some() && self.alternatives.iter().any(|c| matches_chain(c, *node))
+    }
+}
+impl Compound {
+    fn is_empty(&self) -> bool { *self == Compound::default() }
+
+    fn matches(&self, node: Node) -> bool {
+        let Some(name) = node.name() else {
+            return false;
+        };
+        if let Some(n) = &self.name {
+            if !n.eq_ignore_ascii_case(name) {
+                return false;
+            }
+        }
+        if self.id.is_some() && self.id.as_deref() != node.attr("id") {
+            return false;
+        }
+        let classes = node.attr("class").unwrap_or_default();
+        if !self
+            .classes
+            .iter()
+            .all(|c| classes.split_whitespace().any(|n| n == c))
+        {
+            return false;
+        }
+        self.attrs.iter().all(|(attr, cond)| {
+            let Some(value) = node.attr(attr) else {
+                return false;
+            };
+            match cond {
+                None => true,
+                Some(('=', v)) => value == v,
+                Some(('~', v)) => value.split_whitespace().any(|w| w == v),
+                Some(('^', v)) => value.starts_with(v.as_str()),
+                Some(('$', v)) => value.ends_with(v.as_str()),
+                Some(('*', v)) => value.contains(v.as_str()),
+                Some(_) => false,
+            }
+        })
+    }
+}
+
+fn matches_chain(chain: &[(Combinator, Compound)], node: Node) -> bool {
+    let Some(((combinator, last), rest)) = chain.split_last() else {
+        return true;
+    };
+    if !last.matches(node) {
+        return false;
+    }
+    if rest.is_empty() {
+        return true;
+    }
+    match combinator {
+        Combinator::Child => node.parent().is_some_and(|p| matches_chain(rest, p)),
+        Combinator::Descendant => {
+            let mut ancestor = node.parent();
+            while let Some(a) = ancestor {
+                if matches_chain(rest, a) {
+                    return true;
+                }
+                ancestor = a.parent();
+            }
+            false
+        }
+    }
+}
+
+fn ident(chars: &mut Peekable<Chars>) -> String {
+    let mut out = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_alphanumeric() || c == '-' || c == '_' {
+            out.push(c);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+    out
+}
+
+fn parse_attr(chars: &mut Peekable<Chars>) -> Result<(String, Option<(char, String)>), String> {
+    let name = ident(chars);
+    if name.is_empty() {
+        return Err("expected attribute name".to_owned());
+    }
+    let op = match chars.next() {
+        Some(']') => return Ok((name, None)),
+        Some('=') => '=',
+        Some(op @ ('~' | '^' | '$' | '*')) if chars.next() == Some('=') => op,
+        other => return Err(format!("unexpected {:?} in attribute selector", other)),
+    };
+    let value = match chars.peek() {
+        Some(&q @ ('"' | '\'')) => {
+            chars.next();
+            chars.by_ref().take_while(|&c| c != q).collect()
+        }
+        _ => ident(chars),
+    };
+    match chars.next() {
+        Some(']') => Ok((name, Some((op, value)))),
+        other => Err(format!("expected ] got {:?}", other)),
+    }
+}
+
+/// Splits a selector list on the commas outside of brackets and quotes.
+fn split_list(src: &str) -> Vec<&str> {
+    let (mut parts, mut start) = (vec![], 0);
+    let (mut depth, mut quote) = (0usize, None);
+    for (i, c) in src.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => depth = depth.saturating_sub(1),
+            (None, ',') if depth == 0 => {
+                parts.push(&src[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    parts.push(&src[start..]);
+    parts
+}
+
+fn parse_chain(src: &str) -> Result<Vec<(Combinator, Compound)>, String> {
+    let mut chars = src.chars().peekable();
+    let mut chain = vec![];
+    let mut combinator = Combinator::Descendant;
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        match chars.peek() {
+            None => break,
+            Some(&'>') => {
+                chars.next();
+                combinator = Combinator::Child;
+                continue;
+            }
+            _ => (),
+        }
+        let mut compound = Compound::default();
+        let mut universal = false;
+        while let Some(&c) = chars.peek() {
+            match c {
+                '*' => {
+                    chars.next();
+                    universal = true;
+                }
+                '.' => {
+                    chars.next();
+                    compound.classes.push(ident(&mut chars));
+                }
+                '#' => {
+                    chars.next();
+                    compound.id = Some(ident(&mut chars));
+                }
+                '[' => {
+                    chars.next();
+                    compound.attrs.push(parse_attr(&mut chars)?);
+                }
+                c if c.is_alphanumeric() => compound.name = Some(ident(&mut chars)),
+                c if c.is_whitespace() || c == '>' => break,
+                c => return Err(format!("unsupported {:?}", c)),
+            }
+        }
+        if compound.is_empty() && !universal {
+            return Err("empty selector".to_owned());
+        }
+        chain.push((combinator, compound));
+        combinator = Combinator::Descendant;
+    }
+    if chain.is_empty() {
+        return Err("empty selector".to_owned());
+    }
+    Ok(chain)
+}
+
+impl FromStr for Selector {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let alternatives = split_list(s)
+            .into_iter()
+            .map(parse_chain)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::Selector(format!("{}: {}", s, e)))?;
+        Ok(Self {
+            source: s.trim().to_owned(),
+            alternatives,
+        })
+    }
+}
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(&self.source) }
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use time::OffsetDateTime;
+use tokio::{fs::write, io};
+
+/// Metadata of a whole series, as found on its index page.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeriesInfo {
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub authors: Vec<String>,
+    pub artists: Vec<String>,
+    pub alt_titles: Vec<String>,
+    pub genres: Vec<String>,
+    pub description: Option<String>,
+    pub status: Status,
+    pub cover: Option<String>,
+    pub language: Option<String>,
+}
+/// What a finished download holds, kept in its directory so `update` can
+/// fetch only what was added since.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeriesManifest {
+    /// Url the download was started from
+    pub url: String,
+    /// Index the chapter list was read from, if there was one
+    pub index: Option<String>,
+    /// Images were downloaded rather than text
+    pub images: bool,
+    /// Chapters saved completely, in reading order
+    pub chapters: Vec<ChapterEntry>,
+    /// Unix time of the last download
+    pub updated_at: i64,
+}
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChapterEntry {
+    pub url: String,
+    /// Directory name, sequence number included
+    pub name: String,
+    /// Urls of the images or text saved for it
+    pub pages: Vec<String>,
+}
+#[derive(
+    Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ongoing,
+    Completed,
+    Hiatus,
+    Cancelled,
+    #[default]
+    Unknown,
+}
+
+impl SeriesInfo {
+    pub const FILE: &'static str = "series.json";
+
+    /// Writes `series.json` into `dir`.
+    pub async fn save(&self, dir: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        write(dir.join(Self::FILE), json).await
+    }
+
+    pub async fn load(dir: &Path) -> io::Result<Self> {
+        let json = tokio::fs::read(dir.join(Self::FILE)).await?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+impl SeriesManifest {
+    pub const FILE: &'static str = "manifest.json";
+
+    /// Writes `manifest.json` into `dir`, stamped with the current time.
+    pub async fn save(&mut self, dir: &Path) -> io::Result<()> {
+        self.updated_at = OffsetDateTime::now_utc().unix_timestamp();
+        let json = serde_json::to_vec_pretty(self)?;
+        write(dir.join(Self::FILE), json).await
+    }
+
+    pub async fn load(dir: &Path) -> io::Result<Self> {
+        let json = tokio::fs::read(dir.join(Self::FILE)).await?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+
+    pub fn chapter(&self, url: &str) -> Option<&ChapterEntry> {
+        self.chapters.iter().find(|c| c.url == url)
+    }
+}
+impl From<&str> for Status {
+    fn from(s: &str) -> Self {
+        let s = s.to_lowercase();
+        if s.contains("ongoing") || s.contains("publishing") || s.contains("releasing") {
+            Status::Ongoing
+        } else if s.contains("complete") || s.contains("finished") || s.contains("ended") {
+            Status::Completed
+        } else if s.contains("hiatus") {
+            Status::Hiatus
+        } else if s.contains("cancel") || s.contains("dropped") {
+            Status::Cancelled
+        } else {
+            Status::Unknown
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Write, time::Duration};
+
+/// Buckets of a latency histogram, the last one holds anything slower than
+/// 80 seconds.
+const BUCKETS: usize = 64;
+
+/// Per-host request counters kept by a `Retriever` for its lifetime.
+#[derive(Debug, Default)]
+pub struct Stats {
+    hosts: DashMap<String, Counters>,
+}
+#[derive(Debug, Default)]
+struct Counters {
+    requests: u64,
+    bytes: u64,
+    statuses: BTreeMap<u16, u64>,
+    retries: u64,
+    cache_hits: u64,
+    latencies: Histogram,
+}
+/// Latencies counted into buckets a fifth wider than the one before, so the
+/// memory stays the same however long a retriever runs.
+#[derive(Debug, Clone)]
+struct Histogram {
+    counts: [u64; BUCKETS],
+    total: u64,
+    /// Slowest latency in milliseconds
+    max: u64,
+}
+/// Snapshot of one host's counters.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HostStats {
+    pub host: String,
+    pub requests: u64,
+    /// Body bytes received
+    pub bytes: u64,
+    /// Responses by status code
+    pub statuses: BTreeMap<u16, u64>,
+    pub retries: u64,
+    /// Fetches skipped because the page was already visited
+    pub cache_hits: u64,
+    /// Request latency percentiles, including the body, in milliseconds
+    pub p50_ms: u64,
+    pub p90_ms: u64,
+    pub p99_ms: u64,
+}
+
+impl Stats {
+    /// Records a response of `bytes` that took `latency` to read.
+    pub fn response(&self, host: &str, status: u16, bytes: u64, latency: Duration) {
+        let mut c = self.hosts.entry(host.to_owned()).or_default();
+        c.requests += 1;
+        c.bytes += bytes;
+        *c.statuses.entry(status).or_default() += 1;
+        c.latencies.record(latency);
+    }
+
+    pub fn retry(&self, host: &str) { self.hosts.entry(host.to_owned()).or_default().retries += 1; }
+
+    pub fn cache_hit(&self, host: &str) {
+        self.hosts.entry(host.to_owned()).or_default().cache_hits += 1;
+    }
+
+    /// Counters of every host seen so far, sorted by host.
+    pub fn snapshot(&self) -> Vec<HostStats> {
+        let mut out = self
+            .hosts
+            .iter()
+            .map(|e| {
+                let c = e.value();
+                let pct = |p| c.latencies.percentile(p);
+                HostStats {
+                    host: e.key().clone(),
+                    requests: c.requests,
+                    bytes: c.bytes,
+                    statuses: c.statuses.clone(),
+                    retries: c.retries,
+                    cache_hits: c.cache_hits,
+                    p50_ms: pct(50),
+                    p90_ms: pct(90),
+                    p99_ms: pct(99),
+                }
+            })
+            .collect::<Vec<_>>();
+        out.sort_by(|a, b| a.host.cmp(&b.host));
+        out
+    }
+}
+impl Histogram {
+    fn record(&mut self, latency: Duration) {
+        let ms = latency.as_millis() as u64;
+        let i = (0..BUCKETS - 1).find(|&i| ms <= bound(i)).unwrap_or(BUCKETS - 1);
+        self.counts[i] += 1;
+        self.total += 1;
+        self.max = self.max.max(ms);
+    }
+
+    /// Milliseconds `p` percent of the requests took at most, rounded up to
+    /// their bucket.
+    fn percentile(&self, p: u64) -> u64 {
+        let rank = (self.total * p).div_ceil(100).max(1);
+        let mut seen = 0;
+        for (i, n) in self.counts.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return bound(i).min(self.max);
+            }
+        }
+        0
+    }
+}
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: [0; BUCKETS],
+            total: 0,
+            max: 0,
+        }
+    }
+}
+impl HostStats {
+    /// Responses with a status in `class`xx, e.g. 4 for client errors.
+    pub fn class(&self, class: u16) -> u64 {
+        self.statuses
+            .iter()
+            .filter(|(s, _)| **s / 100 == class)
+            .map(|(_, n)| n)
+            .sum()
+    }
+}
+
+/// Human readable summary, one row per host.
+pub fn table(hosts: &[HostStats]) -> String {
+    let mut out = format!(
+        "{:<28} {:>6} {:>10} {:>5} {:>5} {:>5} {:>5} {:>6} {:>6} {:>7} {:>7} {:>7}\n",
+        "HOST", "REQS", "BYTES", "2xx", "3xx", "4xx", "5xx", "RETRY", "CACHE", "p50", "p90", "p99"
+    );
+    for h in hosts {
+        let _ = writeln!(
+            out,
+            "{:<28} {:>6} {:>10} {:>5} {:>5} {:>5} {:>5} {:>6} {:>6} {:>5}ms {:>5}ms {:>5}ms",
+            h.host,
+            h.requests,
+            human(h.bytes),
+            h.class(2),
+            h.class(3),
+            h.class(4),
+            h.class(5),
+            h.retries,
+            h.cache_hits,
+            h.p50_ms,
+            h.p90_ms,
+            h.p99_ms
+        );
+    }
+    out
+}
+
+/// Upper bound of bucket `i` in milliseconds.
+fn bound(i: usize) -> u64 { (i as u64).max(1.2f64.powi(i as i32) as u64) }
+
+fn human(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
@@ -0,0 +1,367 @@
+use crate::{
+    download::{DownloadEvent, Options},
+    error::Error,
+    page::dir_name,
+    retriever::Retriever,
+    series::SeriesManifest,
+};
+use futures::{
+    channel::mpsc::{unbounded, UnboundedSender},
+    future::{ready, Either},
+    pin_mut,
+    stream,
+    FutureExt,
+    Stream,
+    StreamExt,
+};
+#[allow(unused_imports)]
+use log::{debug, info, trace, warn};
+use reqwest::Url;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tokio::{fs::write, io, time::sleep};
+
+/// First wait after a failed check, doubled on each failure in a row and
+/// never longer than the series' interval.
+const RETRY: i64 = 5 * 60;
+
+/// Series followed by `retriever watch`.
+///
+/// ```toml
+/// # relative to this file
+/// library = "library"
+/// interval = "6h"
+/// host_delay = "1m"
+///
+/// [[series]]
+/// url = "https://example.com/series/some-manga"
+/// images = true
+///
+/// [[series]]
+/// url = "https://example.org/novel/some-novel"
+/// name = "Some Novel"
+/// interval = "1d"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// Directory holding a directory per series, and the schedule
+    pub library: PathBuf,
+    /// Time between two checks of a series
+    #[serde(deserialize_with = "interval")]
+    pub interval: Duration,
+    /// Least time between two checks on the same host
+    #[serde(deserialize_with = "interval")]
+    pub host_delay: Duration,
+    /// Pause before each chapter and page request while downloading
+    #[serde(deserialize_with = "interval")]
+    pub delay: Duration,
+    pub series: Vec<Followed>,
+}
+/// One series of a `WatchConfig`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Followed {
+    pub url: Url,
+    /// Download images rather than text
+    #[serde(default)]
+    pub images: bool,
+    /// Directory name in the library, the last part of the url by default
+    pub name: Option<String>,
+    /// Overrides the interval of the config
+    #[serde(default, deserialize_with = "some_interval")]
+    pub interval: Option<Duration>,
+}
+/// When each followed series is checked next, kept in the library so a
+/// restarted watch carries on with the same schedule.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchState {
+    /// Schedule of each series by url
+    pub series: BTreeMap<String, Schedule>,
+    /// Unix time each host was last checked
+    pub hosts: BTreeMap<String, i64>,
+}
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Schedule {
+    /// Unix time of the last check
+    pub last_check: i64,
+    /// Unix time the series is due
+    pub next_check: i64,
+    /// Checks failed in a row
+    pub failures: u32,
+}
+/// Progress of a watch, which runs until the retriever is cancelled.
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// Nothing is due before the unix time
+    Waiting { until: i64 },
+    /// A series is being checked into the directory
+    Checking { url: Url, dir: PathBuf },
+    /// Progress of the download of a check
+    Download { url: Url, event: DownloadEvent },
+    /// A check finished with the chapters it added, the series is due again
+    /// at unix time `next`
+    Checked {
+        url: Url,
+        added: Vec<Url>,
+        failed: bool,
+        next: i64,
+    },
+    Failed(Error),
+}
+
+impl WatchConfig {
+    /// Reads the config at `path`, a relative library is taken from the
+    /// config's directory.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut config: Self = fs::read_to_string(path)?.parse()?;
+        if config.library.is_relative() {
+            let base = path.parent().unwrap_or(Path::new(""));
+            config.library = base.join(&config.library);
+        }
+        Ok(config)
+    }
+
+    /// Where `series` is downloaded.
+    pub fn dir_of(&self, series: &Followed) -> PathBuf {
+        let name = series.name.clone().unwrap_or_else(|| {
+            let mut segments = series.url.path_segments().into_iter().flatten();
+            match segments.rfind(|s| !s.is_empty()) {
+                Some(last) => last.to_owned(),
+                None => series.url.host_str().unwrap_or_default().to_owned(),
+            }
+        });
+        self.library.join(dir_name(&name))
+    }
+
+    pub fn interval_of(&self, series: &Followed) -> Duration {
+        series.interval.unwrap_or(self.interval)
+    }
+}
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            library: PathBuf::from("./"),
+            interval: Duration::from_secs(12 * 60 * 60),
+            host_delay: Duration::from_secs(10),
+            delay: Duration::from_millis(400),
+            series: vec![],
+        }
+    }
+}
+impl FromStr for WatchConfig {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s).map_err(|e| Error::Config(e.to_string()))
+    }
+}
+impl WatchState {
+    pub const FILE: &'static str = "watch.json";
+
+    /// Writes `watch.json` into `dir`.
+    pub async fn save(&self, dir: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        write(dir.join(Self::FILE), json).await
+    }
+
+    pub async fn load(dir: &Path) -> io::Result<Self> {
+        let json = tokio::fs::read(dir.join(Self::FILE)).await?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+
+    /// Unix time `series` may be checked: once due and once its host had
+    /// `host_delay` of rest.
+    pub fn ready_at(&self, series: &Followed, host_delay: Duration) -> i64 {
+        let due = self.series.get(series.url.as_str()).map_or(0, |s| s.next_check);
+        let rested = match series.url.host_str().and_then(|h| self.hosts.get(h)) {
+            Some(last) => last + secs(host_delay),
+            None => 0,
+        };
+        due.max(rested)
+    }
+
+    /// Records a check of `series` at `now` taking `interval`, backing off
+    /// when it failed. Returns when it is due again.
+    pub fn checked(
+        &mut self, series: &Followed, interval: Duration, failed: bool, now: i64,
+    ) -> i64 {
+        let schedule = self.series.entry(series.url.to_string()).or_default();
+        schedule.last_check = now;
+        let wait = if failed {
+            schedule.failures += 1;
+            let backoff = RETRY.saturating_mul(1 << schedule.failures.min(20).saturating_sub(1));
+            backoff.min(secs(interval))
+        } else {
+            schedule.failures = 0;
+            secs(interval)
+        };
+        schedule.next_check = now + wait;
+        if let Some(host) = series.url.host_str() {
+            self.hosts.insert(host.to_owned(), now);
+        }
+        schedule.next_check
+    }
+}
+
+impl Retriever {
+    /// Checks the followed series whenever they are due, downloading new
+    /// chapters into the library, until the retriever is cancelled.
+    ///
+    /// Checks run one at a time, the first download of a series fetches all
+    /// of it and later ones only what `update_series` finds. The schedule is
+    /// saved after every check.
+    pub fn watch(&self, config: WatchConfig) -> impl Stream<Item = WatchEvent> + '_ {
+        let (tx, rx) = unbounded();
+        let work = async move {
+            self.run_watch(config, tx).await;
+            None
+        };
+        stream::select(rx.map(Some), work.into_stream()).filter_map(ready)
+    }
+
+    async fn run_watch(&self, config: WatchConfig, tx: UnboundedSender<WatchEvent>) {
+        let emit = |event| {
+            let _ = tx.unbounded_send(event);
+        };
+        let library = config.library.as_path();
+        if let Err(e) = tokio::fs::create_dir_all(library).await {
+            emit(WatchEvent::Failed(e.into()));
+            return;
+        }
+        let mut state = WatchState::load(library).await.unwrap_or_default();
+        state.series.retain(|url, _| config.series.iter().any(|s| s.url.as_str() == url));
+        while !self.is_cancelled() {
+            let next = config
+                .series
+                .iter()
+                .map(|s| (state.ready_at(s, config.host_delay), s))
+                .min_by_key(|(at, _)| *at);
+            let Some((at, series)) = next else {
+                warn!("No series to watch");
+                return;
+            };
+            let wait = at - now();
+            if wait > 0 {
+                emit(WatchEvent::Waiting { until: at });
+                tokio::select! {
+                    _ = sleep(Duration::from_secs(wait as u64)) => (),
+                    _ = self.cancellation().cancelled() => return,
+                }
+            }
+            let (added, failed) = self.check(&config, series, &tx).await;
+            if self.is_cancelled() {
+                // Checked again first thing after a restart
+                return;
+            }
+            let interval = config.interval_of(series);
+            let next = state.checked(series, interval, failed, now());
+            if let Err(e) = state.save(library).await {
+                emit(WatchEvent::Failed(e.into()));
+            }
+            emit(WatchEvent::Checked {
+                url: series.url.clone(),
+                added,
+                failed,
+                next,
+            });
+        }
+    }
+
+    /// Downloads what is new of `series`, returns the chapters added and
+    /// whether anything failed.
+    async fn check(
+        &self, config: &WatchConfig, series: &Followed, tx: &UnboundedSender<WatchEvent>,
+    ) -> (Vec<Url>, bool) {
+        let emit = |event| {
+            let _ = tx.unbounded_send(event);
+        };
+        let url = series.url.clone();
+        let dir = config.dir_of(series);
+        emit(WatchEvent::Checking {
+            url: url.clone(),
+            dir: dir.clone(),
+        });
+        let mut options = Options::default();
+        options
+            .images(series.images)
+            .output_dir(&dir)
+            .delay(config.delay);
+        let first = !dir.join(SeriesManifest::FILE).exists();
+        let events = if first {
+            Either::Left(self.download_series(url.clone(), options))
+        } else {
+            match self.update_series(options).await {
+                Ok(events) => Either::Right(events),
+                Err(e) => {
+                    emit(WatchEvent::Failed(e.into()));
+                    return (vec![], true);
+                }
+            }
+        };
+        pin_mut!(events);
+        let (mut added, mut failed) = (vec![], false);
+        while let Some(event) = events.next().await {
+            match &event {
+                DownloadEvent::Chapters(chapters) if first => added = chapters.clone(),
+                DownloadEvent::Diff { added: new, .. } => added = new.clone(),
+                DownloadEvent::Failed { url: None, .. } => failed = true,
+                DownloadEvent::Finished { failed: n, .. } if *n > 0 => failed = true,
+                _ => (),
+            }
+            emit(WatchEvent::Download {
+                url: url.clone(),
+                event,
+            });
+        }
+        (added, failed)
+    }
+}
+
+/// Parses a span like `90s`, `30m`, `6h`, `1d` or `400ms`, plain numbers are
+/// seconds.
+pub fn parse_interval(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (num, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(i) => s.split_at(i),
+        None => (s, ""),
+    };
+    let secs = match unit.to_ascii_lowercase().as_str() {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" | "min" => 60.0,
+        "h" => 60.0 * 60.0,
+        "d" => 24.0 * 60.0 * 60.0,
+        other => return Err(format!("unknown unit {:?}", other)),
+    };
+    let num = num
+        .trim()
+        .parse::<f64>()
+        .map_err(|e| format!("{:?}: {}", s, e))?;
+    match num * secs {
+        n if n >= 0.0 && n.is_finite() => Ok(Duration::from_secs_f64(n)),
+        _ => Err(format!("{:?} is not a span of time", s)),
+    }
+}
+
+/// Deserializes a span written for `parse_interval`.
+pub fn interval<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+    parse_interval(&String::deserialize(d)?).map_err(de::Error::custom)
+}
+
+fn some_interval<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+    interval(d).map(Some)
+}
+
+/// Whole seconds of `d`, rounded up.
+fn secs(d: Duration) -> i64 { d.as_secs_f64().ceil() as i64 }
+
+fn now() -> i64 { OffsetDateTime::now_utc().unix_timestamp() }
@@ -0,0 +1,30 @@
+use futures::future::join;
+use retriever::bandwidth::{parse_rate, Bandwidth};
+use std::time::{Duration, Instant};
+
+#[test]
+fn rates() {
+    assert_eq!(parse_rate("512"), Ok(512));
+    assert_eq!(parse_rate("500K"), Ok(500 * 1024));
+    assert_eq!(parse_rate("1.5m"), Ok(3 << 19));
+    assert_eq!(parse_rate("2MiB/s"), Ok(2 << 20));
+    assert!(parse_rate("fast").is_err());
+    assert!(parse_rate("0.1").is_err());
+    assert!(parse_rate("3T").is_err());
+}
+
+#[tokio::test]
+async fn shared_between_readers() {
+    let limit = Bandwidth::new(20_000);
+    let reader = || async {
+        for _ in 0..5 {
+            limit.take(1_000).await;
+        }
+    };
+    let start = Instant::now();
+    join(reader(), reader()).await;
+    // 10 KB at 20 KB/s, whichever reader got which share
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(450), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(1500), "{:?}", elapsed);
+}
@@ -0,0 +1,80 @@
+use reqwest::Url;
+use retriever::{
+    canonical::{canonical_link, normalize, normalize_with, page_key},
+    page::{ContentType, Page},
+};
+use select::document::Document;
+
+fn norm(s: &str) -> String { normalize(s.parse().unwrap()).into() }
+
+#[test]
+fn normalizes() {
+    assert_eq!(norm("HTTP://Example.COM:80/a/b/#comments"), "http://example.com/a/b/");
+    assert_eq!(norm("https://example.com:443/"), "https://example.com/");
+    assert_eq!(norm("https://example.com:8443/x"), "https://example.com:8443/x");
+    assert_eq!(
+        norm("https://example.com/c?utm_source=x&id=3&fbclid=y"),
+        "https://example.com/c?id=3"
+    );
+    assert_eq!(norm("https://example.com/c?utm_medium=feed"), "https://example.com/c");
+    // A query nothing is dropped from is kept as given
+    assert_eq!(norm("https://example.com/c?sig=a~b/c"), "https://example.com/c?sig=a~b/c");
+    assert_eq!(
+        norm("https://example.com/c?q=a%20b&utm_source=x"),
+        "https://example.com/c?q=a%20b"
+    );
+}
+
+#[test]
+fn custom_denylist() {
+    let deny = ["session".to_owned(), "trk*".to_owned()];
+    let url = "https://example.com/c?session=1&trk_a=2&page=2".parse().unwrap();
+    assert_eq!(normalize_with(url, &deny).as_str(), "https://example.com/c?page=2");
+    assert_eq!(norm("https://example.com/c?session=1"), "https://example.com/c?session=1");
+}
+
+#[test]
+fn page_keys() {
+    assert_eq!(page_key("https://example.com/ch/1/"), "https://example.com/ch/1");
+    assert_eq!(page_key("https://example.com/ch/?p=2"), "https://example.com/ch?p=2");
+    assert_eq!(page_key("https://example.com/"), "https://example.com/");
+    assert_eq!(page_key("not a url/"), "not a url/");
+}
+
+#[test]
+fn pages_are_normalized() {
+    let page = Page::try_from("https://Example.com/ch/1/#top".to_owned()).unwrap();
+    assert_eq!(page.url.as_str(), "https://example.com/ch/1/");
+    let page: Page = "https://example.com/ch/1?utm_campaign=rss".parse().unwrap();
+    assert_eq!(page.url.as_str(), "https://example.com/ch/1");
+}
+
+#[test]
+fn deduplicates_pages() {
+    let links = ContentType::Chapters(vec![
+        "https://example.com/ch/1".to_owned(),
+        "https://example.com/ch/1/".to_owned(),
+        "https://example.com/ch/1#comments".to_owned(),
+        "https://example.com/ch/2?utm_source=list".to_owned(),
+        "https://example.com/ch/2".to_owned(),
+    ]);
+    let urls = links
+        .to_pages()
+        .unwrap()
+        .into_iter()
+        .map(|p| p.url.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(urls, ["https://example.com/ch/1", "https://example.com/ch/2"]);
+}
+
+#[test]
+fn canonical() {
+    let base: Url = "https://example.com/ch/1?from=list".parse().unwrap();
+    let doc = Document::from(
+        r#"<html><head><link rel="stylesheet" href="/s.css">
+<link rel="Canonical" href="/ch/1/#x"></head></html>"#,
+    );
+    let url = canonical_link(&doc, &base).unwrap();
+    assert_eq!(url.as_str(), "https://example.com/ch/1/");
+    assert!(canonical_link(&Document::from("<html></html>"), &base).is_none());
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::{retriever, serve, Request, Response};
+use futures::StreamExt;
+use retriever::{page::Page, retriever::Retriever};
+use std::time::Duration;
+
+fn chapter(title: &str, next: &str) -> String {
+    format!(
+        r#"<html><head><title>{}</title></head><body>
+<a class="home" href="/novel">Index</a><p>{} text</p><a class="next" href="{}">Next</a>
+</body></html>"#,
+        title, title, next
+    )
+}
+
+fn route(req: &Request) -> Response {
+    Response::html(match req.path.as_str() {
+        // Relative links, a fragment, then back to the start
+        "/loop/1" => chapter("One", "2"),
+        "/loop/2" => chapter("Two", "/loop/3#top"),
+        "/loop/3" => chapter("Three", "/loop/1"),
+        "/self/1" => chapter("One", "/self/2"),
+        "/self/2" => chapter("Two", "/self/2"),
+        "/last/1" => chapter("One", "/last/2"),
+        "/last/2" => chapter("Two", "/novel"),
+        "/hash/1" => chapter("One", "#"),
+        "/js/1" => chapter("One", "javascript:void(0)"),
+        // Canonically elsewhere, then linked to by that url
+        "/alias/1" => chapter("One", "/alias/2")
+            .replace("</head>", r#"<link rel="canonical" href="/canon/1"></head>"#),
+        "/alias/2" => chapter("Two", "/canon/1"),
+        _ => chapter("Index", "/"),
+    })
+}
+
+async fn setup() -> (Retriever, String) {
+    let addr = serve(route).await;
+    let manifest = r#"
+name = "stub"
+hosts = ["127.0.0.1"]
+[selectors]
+index = { select = "a.home", attr = "href" }
+next = { select = "a.next", attr = "href" }
+links = { select = "a.next", attr = "href" }
+text = { select = "p" }
+"#;
+    let ret = retriever(manifest);
+    (ret, format!("http://{}", addr))
+}
+
+async fn crawl(ret: &Retriever, url: String, max: Option<usize>) -> Vec<String> {
+    let start: Page = url.parse().unwrap();
+    ret.chapters(start, false, max, Duration::ZERO)
+        .map(|p| p.url.path().to_owned())
+        .collect()
+        .await
+}
+
+#[tokio::test]
+async fn stops_on_cycle() {
+    let (ret, base) = setup().await;
+    let paths = crawl(&ret, format!("{}/loop/1", base), None).await;
+    assert_eq!(paths, ["/loop/1", "/loop/2", "/loop/3"]);
+}
+
+#[tokio::test]
+async fn stops_on_self_link() {
+    let (ret, base) = setup().await;
+    let paths = crawl(&ret, format!("{}/self/1", base), None).await;
+    assert_eq!(paths, ["/self/1", "/self/2"]);
+}
+
+#[tokio::test]
+async fn keeps_requested_url() {
+    let (ret, base) = setup().await;
+    let start: Page = format!("{}/alias/1", base).parse().unwrap();
+    let pages = ret.chapters(start, false, None, Duration::ZERO).collect::<Vec<_>>().await;
+    let paths = pages.iter().map(|p| p.url.path()).collect::<Vec<_>>();
+    assert_eq!(paths, ["/alias/1", "/alias/2"]);
+    assert_eq!(pages[0].canonical.as_ref().map(|u| u.path()), Some("/canon/1"));
+    assert_eq!(pages[0].key(), format!("{}/canon/1", base));
+    assert!(pages[1].canonical.is_none());
+}
+
+#[tokio::test]
+async fn stops_at_index() {
+    let (ret, base) = setup().await;
+    let paths = crawl(&ret, format!("{}/last/1", base), None).await;
+    assert_eq!(paths, ["/last/1", "/last/2"]);
+}
+
+#[tokio::test]
+async fn stops_on_placeholder() {
+    let (ret, base) = setup().await;
+    assert_eq!(crawl(&ret, format!("{}/hash/1", base), None).await, ["/hash/1"]);
+    assert_eq!(crawl(&ret, format!("{}/js/1", base), None).await, ["/js/1"]);
+}
+
+#[tokio::test]
+async fn max_chapters() {
+    let (ret, base) = setup().await;
+    let paths = crawl(&ret, format!("{}/loop/1", base), Some(2)).await;
+    assert_eq!(paths, ["/loop/1", "/loop/2"]);
+}
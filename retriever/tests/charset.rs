@@ -0,0 +1,57 @@
+use encoding_rs::{EUC_KR, GBK, SHIFT_JIS, UTF_8, WINDOWS_1252};
+use retriever::{
+    charset::{decode, sniff},
+    extractor::Manifest,
+};
+
+fn html(meta: &str, text: &str) -> String {
+    format!("<html><head>{}<title>t</title></head><body><p>{}</p></body></html>", meta, text)
+}
+
+#[test]
+fn meta_tags() {
+    let body = GBK.encode(&html(r#"<meta charset="gbk">"#, "第一章")).0.into_owned();
+    assert_eq!(sniff(&body, None), GBK);
+    assert!(decode(&body, None, None).contains("第一章"));
+
+    let meta = r#"<META http-equiv="Content-Type" content="text/html; charset=EUC-KR">"#;
+    let body = EUC_KR.encode(&html(meta, "제1장")).0.into_owned();
+    assert_eq!(sniff(&body, None), EUC_KR);
+    assert!(decode(&body, None, None).contains("제1장"));
+}
+
+#[test]
+fn header_before_meta() {
+    let body = SHIFT_JIS.encode(&html(r#"<meta charset="utf-8">"#, "第一話")).0.into_owned();
+    assert_eq!(sniff(&body, Some("Shift_JIS")), SHIFT_JIS);
+    assert!(decode(&body, Some("shift_jis"), None).contains("第一話"));
+}
+
+#[test]
+fn bom_first() {
+    let mut body = b"\xEF\xBB\xBF".to_vec();
+    body.extend_from_slice(html(r#"<meta charset="gbk">"#, "ü").as_bytes());
+    assert_eq!(sniff(&body, Some("windows-1252")), UTF_8);
+    assert!(decode(&body, None, Some(GBK)).contains('ü'));
+}
+
+#[test]
+fn defaults_to_utf8() {
+    assert_eq!(sniff(html("", "ü").as_bytes(), None), UTF_8);
+    assert_eq!(sniff(b"<meta charset=\"utf-16\">", None), UTF_8);
+    assert_eq!(sniff(b"<meta charset=latin1>", None), WINDOWS_1252);
+}
+
+#[test]
+fn site_override() {
+    let body = GBK.encode(&html("", "第一章")).0.into_owned();
+    assert!(!decode(&body, None, None).contains("第一章"));
+
+    let manifest: Manifest = "name = \"old\"\ncharset = \"gb2312\"".parse().unwrap();
+    let extractor = manifest.extractor().unwrap();
+    assert_eq!(extractor.charset(), Some(GBK));
+    assert!(decode(&body, Some("iso-8859-1"), extractor.charset()).contains("第一章"));
+
+    let bad: Manifest = "name = \"bad\"\ncharset = \"klingon\"".parse().unwrap();
+    assert!(bad.extractor().is_err());
+}
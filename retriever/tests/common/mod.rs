@@ -0,0 +1,142 @@
+//! Stub site and scratch directories shared by the integration tests.
+#![allow(dead_code)]
+
+use retriever::{extractor::Manifest, retriever::Retriever};
+use std::{fs, net::SocketAddr, path::PathBuf, sync::Arc};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+pub const NOT_FOUND: &str = "<html><head><title>404</title></head></html>";
+
+/// Site definition for a series index linking chapters of images.
+pub const IMAGE_SITE: &str = r#"
+name = "stub"
+hosts = ["127.0.0.1"]
+[selectors]
+index = { select = "a.home", attr = "href" }
+links = { select = "ul.chapters a", attr = "href" }
+images = { select = "div.pages img", attr = "src" }
+"#;
+
+/// A request as the stub site sees it.
+pub struct Request {
+    /// Path and query
+    pub path: String,
+    /// Head and body as received
+    pub raw: String,
+}
+pub struct Response {
+    pub status: &'static str,
+    pub kind: &'static str,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn html<T: Into<Vec<u8>>>(body: T) -> Self {
+        Self {
+            status: "200 OK",
+            kind: "text/html",
+            headers: vec![],
+            body: body.into(),
+        }
+    }
+
+    pub fn image<T: Into<Vec<u8>>>(body: T) -> Self {
+        Self {
+            kind: "image/png",
+            ..Self::html(body)
+        }
+    }
+
+    pub fn status(mut self, status: &'static str) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// Serves `route` on a free port of 127.0.0.1 for the rest of the test.
+pub async fn serve<F>(route: F) -> SocketAddr
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+{
+    serve_on("127.0.0.1", route).await
+}
+
+/// Serves `route` on a free port of `ip`.
+pub async fn serve_on<F>(ip: &str, route: F) -> SocketAddr
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind((ip, 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let route = Arc::new(route);
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let route = route.clone();
+            tokio::spawn(async move { answer(stream, &*route).await });
+        }
+    });
+    addr
+}
+
+async fn answer<F: Fn(&Request) -> Response>(mut stream: TcpStream, route: &F) {
+    let mut buf = vec![0; 8192];
+    let mut len = 0;
+    // Read until the whole body announced by Content-Length is in
+    let raw = loop {
+        match stream.read(&mut buf[len..]).await.unwrap() {
+            0 => return,
+            n => len += n,
+        }
+        let raw = String::from_utf8_lossy(&buf[..len]).into_owned();
+        if let Some((head, body)) = raw.split_once("\r\n\r\n") {
+            let want = head
+                .lines()
+                .find_map(|l| l.to_lowercase().strip_prefix("content-length: ").map(str::to_owned))
+                .and_then(|l| l.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            if body.len() >= want {
+                break raw;
+            }
+        }
+    };
+    let path = raw.split_whitespace().nth(1).unwrap_or("/").to_owned();
+    let res = route(&Request { path, raw });
+    let mut head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n",
+        res.status,
+        res.kind,
+        res.body.len()
+    );
+    for (name, value) in &res.headers {
+        head += &format!("{}: {}\r\n", name, value);
+    }
+    head += "Connection: close\r\n\r\n";
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(&res.body).await.unwrap();
+}
+
+/// A retriever knowing the site defined by `manifest`.
+pub fn retriever(manifest: &str) -> Retriever {
+    let manifest: Manifest = manifest.parse().unwrap();
+    let mut ret = Retriever::default();
+    ret.add_manifest(manifest).unwrap();
+    ret
+}
+
+/// An empty directory for the test `name`, unique to this run.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("retriever-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
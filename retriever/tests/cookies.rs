@@ -0,0 +1,84 @@
+mod common;
+
+use common::{serve, Response};
+use reqwest::Url;
+use retriever::client::{parse_cookies_txt, parse_header, ClientConfig, CookieJar};
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+    thread::sleep,
+    time::Duration,
+};
+
+#[test]
+fn netscape_cookies() {
+    let src = "# Netscape HTTP Cookie File\n\
+               .example.com\tTRUE\t/\tTRUE\t0\tsession\tabc\n\
+               #HttpOnly_example.org\tFALSE\t/manga\tFALSE\t4102444800\tage_ok\t1\n\
+               example.net\tFALSE\t/\tFALSE\t1\told\tgone\n";
+    let cookies = parse_cookies_txt(src).unwrap();
+    assert_eq!(cookies.len(), 2);
+    assert_eq!(cookies[0].0, "session=abc; Path=/; Domain=example.com; Secure");
+    assert_eq!(cookies[0].1.as_str(), "https://example.com/");
+    assert!(cookies[1].0.starts_with("age_ok=1; Path=/manga; Max-Age="));
+    assert!(cookies[1].0.ends_with("; HttpOnly"));
+    assert!(parse_cookies_txt("broken line").is_err());
+}
+
+#[test]
+fn headers() {
+    assert_eq!(
+        parse_header("Referer: https://example.com/"),
+        Ok(("Referer".to_owned(), "https://example.com/".to_owned()))
+    );
+    assert_eq!(parse_header("X-Empty:"), Ok(("X-Empty".to_owned(), String::new())));
+    assert!(parse_header("Referer").is_err());
+    assert!(parse_header("Bad Name: x").is_err());
+}
+
+#[tokio::test]
+async fn repeated_headers() {
+    let seen = Arc::new(Mutex::new(String::new()));
+    let raw = seen.clone();
+    let addr = serve(move |req| {
+        *raw.lock().unwrap() = req.raw.to_lowercase();
+        Response::html("")
+    })
+    .await;
+    let mut config = ClientConfig::default();
+    config.header("X-Tag", "a").header("X-Tag", "b").header("X-Other", "c");
+    config.header("Cookie", "a=1").header("Cookie", "b=2");
+    let (client, _) = config.build().unwrap();
+    client.get(format!("http://{}/", addr)).send().await.unwrap();
+    let raw = seen.lock().unwrap().clone();
+    assert!(raw.contains("x-tag: a, b\r\n"));
+    assert!(raw.contains("x-other: c\r\n"));
+    assert!(raw.contains("cookie: a=1; b=2\r\n"));
+}
+
+#[test]
+fn jar_round_trip() {
+    let path = std::env::temp_dir()
+        .join(format!("retriever-cookies-{}", std::process::id()))
+        .join("cookies.json");
+    let url: Url = "https://example.com/manga/1".parse().unwrap();
+    let jar = CookieJar::default();
+    jar.add_cookie_str("session=abc; Path=/", &url);
+    jar.add_cookie_str("age_ok=1; Path=/; Max-Age=3600", &url);
+    jar.add_cookie_str("flash=1; Path=/; Max-Age=1", &url);
+    sleep(Duration::from_millis(1100));
+    jar.save(&path).unwrap();
+    // Expired cookies are pruned before writing
+    assert!(!jar.has_cookie(&url, "flash"));
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+    let loaded = CookieJar::load(&path).unwrap();
+    assert!(loaded.has_cookie(&url, "session"));
+    assert!(loaded.has_cookie(&url, "age_ok"));
+    assert!(!loaded.has_cookie(&url, "flash"));
+    assert!(!loaded.has_cookie(&"https://example.org/".parse().unwrap(), "session"));
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+    let missing = CookieJar::load(&path).unwrap();
+    assert!(!missing.has_cookie(&url, "session"));
+}
@@ -0,0 +1,187 @@
+mod common;
+
+use common::{retriever, serve, temp_dir, Request, Response, IMAGE_SITE, NOT_FOUND};
+use futures::StreamExt;
+use retriever::{
+    dedup::{Dedup, DedupMode, HashIndex, Verdict},
+    download::{DownloadEvent, Options},
+};
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+const SERIES: &str = r#"<html><head><title>Series</title></head><body>
+<a class="home" href="/series">Series</a>
+<ul class="chapters"><li><a href="/ch/1">One</a></li><li><a href="/ch/2">Two</a></li>
+<li><a href="/ch/3">Three</a></li></ul></body></html>"#;
+
+fn chapter(n: &str, credits: &str) -> String {
+    format!(
+        r#"<html><head><title>{0}</title></head><body>
+<div class="pages"><img src="/img/{0}.png"/><img src="/img/{1}.png"/></div></body></html>"#,
+        n, credits
+    )
+}
+
+fn route(req: &Request) -> Response {
+    match req.path.as_str() {
+        "/series" => Response::html(SERIES),
+        p if p.starts_with("/ch/") => {
+            Response::html(chapter(&p[4..], &format!("credits-{}", &p[4..])))
+        }
+        // Every chapter ends on the same credits page
+        p if p.starts_with("/img/credits") => Response::image("credits"),
+        p if p.starts_with("/img/") => Response::image(p),
+        _ => Response::html(NOT_FOUND),
+    }
+}
+
+/// `route`, but the chapters share a single credits page url.
+fn shared_route(req: &Request) -> Response {
+    match req.path.as_str() {
+        p if p.starts_with("/ch/") => Response::html(chapter(&p[4..], "credits")),
+        _ => route(req),
+    }
+}
+
+async fn download(name: &str, dedup: Dedup) -> (PathBuf, Vec<DownloadEvent>) {
+    download_from(route, name, dedup).await
+}
+
+async fn download_from(
+    route: fn(&Request) -> Response, name: &str, dedup: Dedup,
+) -> (PathBuf, Vec<DownloadEvent>) {
+    let addr = serve(route).await;
+    let ret = retriever(IMAGE_SITE);
+    let dir = temp_dir(&format!("dedup-{}", name));
+    let mut options = Options::default();
+    options.images(true).output_dir(&dir).dedup(dedup);
+
+    let url = format!("http://{}/series", addr).parse().unwrap();
+    let events = ret.download_series(url, options).collect::<Vec<_>>().await;
+    (dir, events)
+}
+
+fn credits(dir: &Path) -> Vec<PathBuf> {
+    ["001 1", "002 2", "003 3"]
+        .iter()
+        .map(|ch| dir.join(ch).join("002.png"))
+        .filter(|p| p.exists())
+        .collect()
+}
+
+fn duplicates(events: &[DownloadEvent]) -> usize {
+    events
+        .iter()
+        .filter(|e| matches!(e, DownloadEvent::Duplicate { .. }))
+        .count()
+}
+
+#[test]
+fn modes() {
+    assert_eq!("skip".parse(), Ok(DedupMode::Skip));
+    assert_eq!("Hardlink".parse(), Ok(DedupMode::Hardlink));
+    assert_eq!("link".parse(), Ok(DedupMode::Hardlink));
+    assert!("copy".parse::<DedupMode>().is_err());
+    assert!(Dedup::default().is_off());
+}
+
+#[test]
+fn verdicts() {
+    let root = temp_dir("dedup-index");
+    let (a, b, c) = (Path::new("a.png"), Path::new("b.png"), Path::new("c.png"));
+    let skip = Dedup {
+        mode: DedupMode::Skip,
+        drop_after: Some(2),
+    };
+    let mut index = HashIndex::default();
+    assert_eq!(index.check(b"x", &root, a, "ch/1", &skip), Verdict::Write);
+    fs::write(root.join(a), "x").unwrap();
+    // Saving the same file again, as on a retry, is no duplicate
+    assert_eq!(index.check(b"x", &root, a, "ch/1", &skip), Verdict::Write);
+    assert_eq!(index.check(b"x", &root, b, "ch/2", &skip), Verdict::Skip(a.to_owned()));
+    assert_eq!(index.check(b"y", &root, b, "ch/2", &skip), Verdict::Write);
+    assert_eq!(index.check(b"x", &root, c, "ch/3", &skip), Verdict::Drop(vec![a.to_owned()]));
+    assert_eq!(index.check(b"x", &root, c, "ch/4", &skip), Verdict::Drop(vec![]));
+    assert!(index.is_dropped(b"x"));
+    assert!(!index.is_dropped(b"y"));
+
+    // A deleted copy is forgotten rather than linked to
+    let link = Dedup {
+        mode: DedupMode::Hardlink,
+        drop_after: None,
+    };
+    assert_eq!(index.check(b"y", &root, c, "ch/3", &link), Verdict::Write);
+    fs::write(root.join(c), "y").unwrap();
+    assert_eq!(index.check(b"y", &root, a, "ch/4", &link), Verdict::Link(c.to_owned()));
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn skips_duplicates() {
+    let dedup = Dedup {
+        mode: DedupMode::Skip,
+        drop_after: None,
+    };
+    let (dir, events) = download("skip", dedup).await;
+    assert_eq!(credits(&dir).len(), 1);
+    assert_eq!(duplicates(&events), 2);
+    assert!(dir.join("003 3").join("001.png").exists());
+    let index = HashIndex::load(&dir).await.unwrap();
+    let credits = index.hashes.values().find(|s| s.chapters.len() == 3).unwrap();
+    assert_eq!(credits.files.len(), 1);
+    assert!(events.iter().any(|e| matches!(e, DownloadEvent::Finished { failed: 0, .. })));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn links_duplicates() {
+    let dedup = Dedup {
+        mode: DedupMode::Hardlink,
+        drop_after: None,
+    };
+    let (dir, events) = download("link", dedup).await;
+    let credits = credits(&dir);
+    assert_eq!(credits.len(), 3);
+    assert_eq!(duplicates(&events), 2);
+    let inode = fs::metadata(&credits[0]).unwrap().ino();
+    assert!(credits.iter().all(|p| fs::metadata(p).unwrap().ino() == inode));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn drops_repeated() {
+    let dedup = Dedup {
+        mode: DedupMode::Off,
+        drop_after: Some(2),
+    };
+    let (dir, _) = download("drop", dedup).await;
+    assert!(credits(&dir).is_empty());
+    for ch in ["001 1", "002 2", "003 3"] {
+        assert!(dir.join(ch).join("001.png").exists());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn shared_credits_url() {
+    let dedup = Dedup {
+        mode: DedupMode::Skip,
+        drop_after: None,
+    };
+    let (dir, events) = download_from(shared_route, "shared", dedup).await;
+    assert_eq!(credits(&dir).len(), 1);
+    assert_eq!(duplicates(&events), 2);
+    let chapters = events
+        .iter()
+        .filter(|e| matches!(e, DownloadEvent::ChapterSaved { .. }))
+        .count();
+    assert_eq!(chapters, 3);
+    assert!(matches!(
+        events.last(),
+        Some(DownloadEvent::Finished { saved: 4, failed: 0 })
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+}
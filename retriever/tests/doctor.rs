@@ -0,0 +1,45 @@
+use retriever::{
+    doctor::{diagnose, Outcome, Report},
+    extractor::Extractor,
+    page::Page,
+};
+
+const PAGE: &str = r#"<html><head><title>Chapter 1</title></head><body>
+<a class="next" href="/ch/2">Next</a><p>Once upon a time</p></body></html>"#;
+
+fn page() -> Page {
+    let mut page: Page = "https://example.com/ch/1".parse().unwrap();
+    page.html = Some(PAGE.to_owned());
+    page
+}
+
+fn outcome<'a>(reports: &'a [Report], field: &str) -> &'a Outcome {
+    &reports.iter().find(|r| r.field == field).unwrap().outcome
+}
+
+#[tokio::test]
+async fn outcomes() {
+    let mut extractor = Extractor::new();
+    extractor.set_title(None);
+    extractor.set_next(Some(|_| Some("/ch/2".to_owned())));
+    extractor.set_links(Some(|_| {
+        Some(vec!["https://example.com/ch/1".to_owned(), "/ch/2".to_owned()])
+    }));
+    extractor.set_images(Some(|_| panic!("no images here")));
+    let reports = diagnose(&extractor, &page()).await;
+
+    assert_eq!(*outcome(&reports, "title"), Outcome::Empty);
+    // Next links are followed from the page, relative ones are fine
+    assert_eq!(*outcome(&reports, "next"), Outcome::Values(vec!["/ch/2".to_owned()]));
+    assert_eq!(
+        *outcome(&reports, "links"),
+        Outcome::BadUrls(
+            vec!["https://example.com/ch/1".to_owned(), "/ch/2".to_owned()],
+            vec!["/ch/2".to_owned()]
+        )
+    );
+    assert_eq!(*outcome(&reports, "images"), Outcome::Panicked("no images here".to_owned()));
+    let links = reports.iter().find(|r| r.field == "links").unwrap();
+    assert!(!links.is_ok());
+    assert_eq!(links.count(), 2);
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::{retriever, serve, temp_dir, Request, Response, IMAGE_SITE, NOT_FOUND};
+use futures::StreamExt;
+use retriever::download::{DownloadEvent, Options};
+use std::fs;
+
+const SERIES: &str = r#"<html><head><title>Series</title></head><body>
+<a class="home" href="/series">Series</a>
+<ul class="chapters"><li><a href="/ch/1">One</a></li><li><a href="/ch/2">Two</a></li></ul>
+</body></html>"#;
+const CH1: &str = r#"<html><head><title>One</title></head><body>
+<div class="pages"><img src="/img/a1.png"/><img src="/img/a2.png"/></div></body></html>"#;
+const CH2: &str = r#"<html><head><title>Two</title></head><body>
+<div class="pages"><img src="/img/b1.png"/></div></body></html>"#;
+
+fn route(req: &Request) -> Response {
+    match req.path.as_str() {
+        "/series" => Response::html(SERIES),
+        "/ch/1" => Response::html(CH1),
+        "/ch/2" => Response::html(CH2),
+        p if p.starts_with("/img/") => Response::image("not really a png"),
+        _ => Response::html(NOT_FOUND),
+    }
+}
+
+#[tokio::test]
+async fn image_series() {
+    let addr = serve(route).await;
+    let ret = retriever(IMAGE_SITE);
+    let dir = temp_dir("download");
+    let mut options = Options::default();
+    options.images(true).output_dir(&dir);
+
+    let url = format!("http://{}/series", addr).parse().unwrap();
+    let events = ret.download_series(url, options).collect::<Vec<_>>().await;
+    assert!(matches!(events.first(), Some(DownloadEvent::Started(_))));
+    let chapters = events.iter().find_map(|e| match e {
+        DownloadEvent::Chapters(c) => Some(c.len()),
+        _ => None,
+    });
+    assert_eq!(chapters, Some(2));
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, DownloadEvent::Queued { total: 3, skipped: 0 })));
+    let saved = events
+        .iter()
+        .filter(|e| matches!(e, DownloadEvent::Saved { total: 3, .. }))
+        .count();
+    assert_eq!(saved, 3);
+    assert!(matches!(
+        events.last(),
+        Some(DownloadEvent::Finished { saved: 3, failed: 0 })
+    ));
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, DownloadEvent::Tree(t) if t.leaves().len() == 3)));
+    for name in ["001 One/001.png", "001 One/002.png", "002 Two/001.png"] {
+        assert_eq!(fs::read(dir.join(name)).unwrap(), b"not really a png");
+    }
+    let sources = fs::read_to_string(dir.join("002 Two").join("sources.lst")).unwrap();
+    assert_eq!(sources, format!("http://{}/img/b1.png\n", addr));
+    let chapters = fs::read_to_string(dir.join("sources.lst")).unwrap();
+    assert_eq!(chapters.lines().count(), 2);
+    assert!(!dir.join("resume.json").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn unreachable_series() {
+    // Nothing listens on the port once the listener is gone
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let ret = retriever(IMAGE_SITE);
+    let dir = temp_dir("download-unreachable");
+    let mut options = Options::default();
+    options.images(true).output_dir(&dir);
+
+    let url: url::Url = format!("http://{}/series", addr).parse().unwrap();
+    let events = ret.download_series(url.clone(), options).collect::<Vec<_>>().await;
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, DownloadEvent::Failed { url: Some(at), .. } if *at == url)));
+    assert!(matches!(
+        events.last(),
+        Some(DownloadEvent::Finished { saved: 0, failed: 1 })
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn truncated_series() {
+    // Announces more than it sends, then hangs up
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let _ = stream.read(&mut [0; 8192]).await;
+            let head = "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 1000\r\n\r\n";
+            let _ = stream.write_all(format!("{}<html><head>", head).as_bytes()).await;
+        }
+    });
+    let ret = retriever(IMAGE_SITE);
+    let dir = temp_dir("download-truncated");
+    let mut options = Options::default();
+    options.images(true).output_dir(&dir);
+
+    let url: url::Url = format!("http://{}/series", addr).parse().unwrap();
+    let events = ret.download_series(url.clone(), options).collect::<Vec<_>>().await;
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, DownloadEvent::Failed { url: Some(at), .. } if *at == url)));
+    assert!(matches!(
+        events.last(),
+        Some(DownloadEvent::Finished { saved: 0, failed: 1 })
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,219 @@
+mod common;
+
+use common::{retriever, serve, temp_dir, Request, Response, IMAGE_SITE, NOT_FOUND};
+use futures::StreamExt;
+use retriever::{
+    download::{DownloadEvent, Options},
+    hooks::{CommandLine, HookEvent, HookKind, Hooks},
+};
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+const SERIES: &str = r#"<html><head><title>Series</title></head><body>
+<a class="home" href="/series">Series</a>
+<ul class="chapters"><li><a href="/ch/1">One</a></li><li><a href="/ch/2">Two</a></li></ul>
+</body></html>"#;
+const CH1: &str = r#"<html><head><title>One</title></head><body>
+<div class="pages"><img src="/img/a1.png"/><img src="/img/a2.png"/></div></body></html>"#;
+const CH2: &str = r#"<html><head><title>Two</title></head><body>
+<div class="pages"><img src="/img/b1.png"/></div></body></html>"#;
+
+fn route(req: &Request) -> Response {
+    match req.path.as_str() {
+        "/series" => Response::html(SERIES),
+        "/ch/1" => Response::html(CH1),
+        "/ch/2" => Response::html(CH2),
+        p if p.starts_with("/img/") => Response::image("not really a png"),
+        _ => Response::html(NOT_FOUND),
+    }
+}
+
+#[test]
+fn config() {
+    let hooks: Hooks = r#"
+[[hook]]
+on = ["series_updated"]
+command = "notify-send new"
+[[hook]]
+on = ["chapter_complete", "run_failed"]
+command = ["sync", "--now"]
+stdin = true
+timeout = "2m"
+"#
+    .parse()
+    .unwrap();
+    let (first, second) = (&hooks.hooks[0], &hooks.hooks[1]);
+    assert_eq!(first.command, CommandLine::Shell("notify-send new".to_owned()));
+    assert!(!first.stdin);
+    assert_eq!(first.timeout, Duration::from_secs(30));
+    assert_eq!(second.on, [HookKind::ChapterComplete, HookKind::RunFailed]);
+    assert_eq!(second.timeout, Duration::from_secs(120));
+    assert!("[[hook]]\non = [\"finished\"]\ncommand = \"true\"".parse::<Hooks>().is_err());
+}
+
+#[tokio::test]
+async fn env_and_stdin() {
+    let dir = temp_dir("hooks-env");
+    let out = dir.join("out");
+    let hooks: Hooks = format!(
+        r#"
+[[hook]]
+on = ["series_updated"]
+command = 'echo $RETRIEVER_EVENT $RETRIEVER_ADDED_COUNT > "{0}"; cat >> "{0}"'
+stdin = true
+[[hook]]
+on = ["run_failed"]
+command = 'echo wrong > "{0}"'
+"#,
+        out.display()
+    )
+    .parse()
+    .unwrap();
+    let event = HookEvent::SeriesUpdated {
+        series: "https://example.com/series".to_owned(),
+        dir: dir.clone(),
+        added: vec!["https://example.com/ch/3".to_owned(), "https://example.com/ch/4".to_owned()],
+    };
+    hooks.fire(&event).await;
+    let written = fs::read_to_string(&out).unwrap();
+    let (env, json) = written.split_once('\n').unwrap();
+    assert_eq!(env, "series_updated 2");
+    let json: serde_json::Value = serde_json::from_str(json).unwrap();
+    assert_eq!(json["event"], "series_updated");
+    assert_eq!(json["added"][1], "https://example.com/ch/4");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn status_and_timeout() {
+    let hooks: Hooks = r#"
+[[hook]]
+on = ["run_failed"]
+command = ["sh", "-c", "exit 3"]
+[[hook]]
+on = ["run_failed"]
+command = "sleep 5"
+timeout = "100ms"
+"#
+    .parse()
+    .unwrap();
+    let event = HookEvent::RunFailed {
+        series: "https://example.com/series".to_owned(),
+        dir: PathBuf::from("."),
+        failed: 1,
+        errors: vec!["no content".to_owned()],
+    };
+    let status = hooks.hooks[0].run(&event).await.unwrap();
+    assert_eq!(status.code(), Some(3));
+    let start = Instant::now();
+    let err = hooks.hooks[1].run(&event).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    assert!(start.elapsed() < Duration::from_secs(4));
+}
+
+#[tokio::test]
+async fn fired_by_downloads() {
+    let addr = serve(route).await;
+    let dir = temp_dir("hooks-download");
+    let log = dir.join("events.log");
+    let hooks: Hooks = format!(
+        r#"
+[[hook]]
+on = ["chapter_complete", "series_updated"]
+command = 'echo $RETRIEVER_EVENT $RETRIEVER_CHAPTER$RETRIEVER_ADDED_COUNT >> "{}"'
+"#,
+        log.display()
+    )
+    .parse()
+    .unwrap();
+    let mut ret = retriever(IMAGE_SITE);
+    ret.set_hooks(hooks);
+    let mut options = Options::default();
+    options.images(true).output_dir(dir.join("series"));
+
+    let url = format!("http://{}/series", addr).parse().unwrap();
+    let events = ret.download_series(url, options).collect::<Vec<_>>().await;
+    let saved = events
+        .iter()
+        .filter(|e| matches!(e, DownloadEvent::ChapterSaved { .. }))
+        .count();
+    assert_eq!(saved, 2);
+    let mut lines = fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    assert_eq!(lines.pop().unwrap(), "series_updated 2");
+    lines.sort();
+    assert_eq!(lines, [
+        format!("chapter_complete http://{}/ch/1", addr),
+        format!("chapter_complete http://{}/ch/2", addr),
+    ]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn partly_failed_run() {
+    // The page of the second chapter is on a port nothing listens on
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let ch2 = CH2.replace("/img/b1.png", &format!("http://{}/b1.png", closed));
+    let addr = serve(move |req: &Request| match req.path.as_str() {
+        "/ch/2" => Response::html(ch2.clone()),
+        _ => route(req),
+    })
+    .await;
+    let dir = temp_dir("hooks-partial");
+    let log = dir.join("events.log");
+    let hooks: Hooks = format!(
+        r#"
+[[hook]]
+on = ["series_updated", "run_failed"]
+command = 'echo $RETRIEVER_EVENT $RETRIEVER_ADDED$RETRIEVER_FAILED >> "{}"'
+"#,
+        log.display()
+    )
+    .parse()
+    .unwrap();
+    let mut ret = retriever(IMAGE_SITE);
+    ret.set_hooks(hooks);
+    let mut options = Options::default();
+    options.images(true).output_dir(dir.join("series"));
+
+    let url = format!("http://{}/series", addr).parse().unwrap();
+    ret.download_series(url, options).collect::<Vec<_>>().await;
+    let log = fs::read_to_string(&log).unwrap();
+    assert_eq!(log, format!("series_updated http://{}/ch/1\nrun_failed 1\n", addr));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn unwritable_output_dir() {
+    let addr = serve(route).await;
+    let dir = temp_dir("hooks-unwritable");
+    let log = dir.join("events.log");
+    // A file is in the way of the output directory
+    fs::write(dir.join("taken"), "").unwrap();
+    let hooks: Hooks = format!(
+        r#"
+[[hook]]
+on = ["run_failed"]
+command = 'echo $RETRIEVER_EVENT $RETRIEVER_FAILED >> "{}"'
+"#,
+        log.display()
+    )
+    .parse()
+    .unwrap();
+    let mut ret = retriever(IMAGE_SITE);
+    ret.set_hooks(hooks);
+    let mut options = Options::default();
+    options.images(true).output_dir(dir.join("taken").join("series"));
+
+    let url = format!("http://{}/series", addr).parse().unwrap();
+    let events = ret.download_series(url, options).collect::<Vec<_>>().await;
+    assert!(matches!(events.first(), Some(DownloadEvent::Failed { url: None, .. })));
+    assert_eq!(fs::read_to_string(&log).unwrap(), "run_failed 0\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,45 @@
+use retriever::{
+    extractor::{Field, Manifest},
+    infer::infer,
+    page::Page,
+};
+
+const PAGE: &str = r#"<html><body>
+<nav><a href="/">Home</a><a href="/latest">Latest</a></nav>
+<div class="content"><h1 class="title">Some   Series</h1>
+<ul class="chapters">
+<li class="item-3"><a href="/read/3">Chapter 3</a></li>
+<li><a href="/read/2">Chapter 2</a></li>
+<li><a href="https://example.com/read/1">Chapter 1</a></li>
+</ul></div></body></html>"#;
+
+#[tokio::test]
+async fn links() {
+    let base = "https://example.com/series/some".parse().unwrap();
+    let candidates = infer(PAGE, &base, "/read/2", Field::Links);
+    let best = &candidates[0];
+    assert_eq!(best.pick.select, "li a");
+    assert_eq!(best.pick.attr.as_deref(), Some("href"));
+    assert_eq!(best.values, [
+        "https://example.com/read/3",
+        "https://example.com/read/2",
+        "https://example.com/read/1"
+    ]);
+    assert!(candidates.iter().any(|c| c.matches() == 5));
+
+    let manifest = best.snippet(Field::Links).parse::<Manifest>().unwrap();
+    let mut page = Page::from(base);
+    page.html = Some(PAGE.to_owned());
+    let links = manifest.extractor().unwrap().get_links(&page).await;
+    assert_eq!(links.as_deref(), Some(best.values.as_slice()));
+}
+
+#[test]
+fn title() {
+    let base = "https://example.com/series/some".parse().unwrap();
+    let candidates = infer(PAGE, &base, "Some Series", Field::Title);
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].pick.select, "h1");
+    assert_eq!(candidates[0].pick.attr, None);
+    assert!(infer(PAGE, &base, "Missing", Field::Title).is_empty());
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{retriever, serve, Request, Response};
+use retriever::{
+    extractor::Extractor,
+    page::{ContentType, Page},
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+const FORM: &str = r#"<html><head><title>Login</title></head><body><div><p>Please log in</p>
+<form action="/session" method="post">
+<input type="hidden" name="csrf" value="token"/>
+<input name="user"/><input type="password" name="pass"/>
+</form></div></body></html>"#;
+const CHAPTER: &str = r#"<html><head><title>Chapter</title></head><body>
+<div><p>members only</p></div></body></html>"#;
+
+/// Answers `/session` logins and serves `/chapter` to the latest session,
+/// each session being good for a single chapter.
+fn route(req: &Request, logins: &AtomicUsize, used: &AtomicUsize) -> Response {
+    let req = &req.raw;
+    let session = format!("session={}", logins.load(Ordering::SeqCst));
+    if req.starts_with("POST /session") {
+        assert!(req.contains("csrf=token"));
+        assert!(req.contains("user=alice") && req.contains("pass=secret"));
+        let n = logins.fetch_add(1, Ordering::SeqCst) + 1;
+        used.store(0, Ordering::SeqCst);
+        Response::html("Welcome").header("Set-Cookie", format!("session={}; Path=/", n))
+    } else if req.starts_with("GET /chapter") &&
+        req.contains(&session) &&
+        used.fetch_add(1, Ordering::SeqCst) == 0
+    {
+        Response::html(CHAPTER)
+    } else {
+        Response::html(FORM)
+    }
+}
+
+#[tokio::test]
+async fn form_login() {
+    let logins = Arc::new(AtomicUsize::new(0));
+    let used = Arc::new(AtomicUsize::new(0));
+    let (l, u) = (logins.clone(), used.clone());
+    let addr = serve(move |req| route(req, &l, &u)).await;
+    std::env::set_var("STUB_USER", "alice");
+    std::env::set_var("STUB_PASS", "secret");
+    let manifest = format!(
+        r#"
+name = "stub"
+hosts = ["127.0.0.1"]
+[login]
+url = "http://{addr}/login"
+username_field = "user"
+password_field = "pass"
+username_env = "STUB_USER"
+password_env = "STUB_PASS"
+success = "Welcome"
+"#
+    );
+    let ret = retriever(&manifest);
+    let extractor = Extractor::default();
+    let url = format!("http://{addr}/chapter");
+    for expected_logins in [1, 2] {
+        let mut page: Page = url.parse().unwrap();
+        ret.fetch(&mut page, &extractor, false).await.unwrap();
+        assert_eq!(logins.load(Ordering::SeqCst), expected_logins);
+        match &page.content.data {
+            Some(ContentType::Text(text, _)) => assert!(text.concat().contains("members only")),
+            other => panic!("unexpected content: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use common::{retriever, serve_on, Response};
+use retriever::{page::Page, retriever::Retriever};
+
+const PAGE: &str = "<html><head><title>Chapter</title></head><body><div><p>text</p></div></body></html>";
+
+/// Serves every path on `ip`, or redirects it for good to `moved_to`.
+async fn listen(ip: &str, moved_to: Option<String>) -> u16 {
+    let addr = serve_on(ip, move |req| match &moved_to {
+        Some(to) => Response::html("")
+            .status("301 Moved Permanently")
+            .header("Location", format!("{}{}", to, req.path)),
+        None => Response::html(PAGE),
+    })
+    .await;
+    addr.port()
+}
+
+#[tokio::test]
+async fn falls_back_to_mirror() {
+    let port = listen("127.0.0.2", None).await;
+    let manifest = r#"
+name = "stub"
+hosts = ["127.0.0.3"]
+mirrors = ["127.0.0.2"]
+"#;
+    let ret = retriever(manifest);
+
+    let mut page: Page = format!("http://127.0.0.3:{}/ch/1", port).parse().unwrap();
+    ret.check_page(&mut page, false).await.unwrap();
+    assert_eq!(page.url.as_str(), format!("http://127.0.0.3:{}/ch/1", port));
+    assert_eq!(page.content().name().map(String::as_str), Some("Chapter"));
+
+    // Nothing is remembered, each page falls back on its own
+    let later = format!("http://127.0.0.3:{}/ch/2", port).parse().unwrap();
+    assert_eq!(ret.relocate(&later), later);
+    assert!(ret.moves().is_empty());
+    let mut page = Page::from(later);
+    ret.check_page(&mut page, false).await.unwrap();
+    assert_eq!(page.content().name().map(String::as_str), Some("Chapter"));
+}
+
+#[tokio::test]
+async fn follows_permanent_move() {
+    let new = listen("127.0.0.4", None).await;
+    let old = listen("127.0.0.5", Some(format!("http://127.0.0.4:{}", new))).await;
+    let ret = Retriever::default();
+
+    let mut page: Page = format!("http://127.0.0.5:{}/ch/1", old).parse().unwrap();
+    ret.check_page(&mut page, false).await.unwrap();
+    assert_eq!(page.url.as_str(), format!("http://127.0.0.4:{}/ch/1", new));
+    assert_eq!(
+        ret.moves(),
+        [("127.0.0.5".to_owned(), ("127.0.0.4".to_owned(), Some(new)))]
+    );
+}
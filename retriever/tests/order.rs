@@ -0,0 +1,42 @@
+use retriever::order::{chapter_number, reading_order, sequence, ListOrder};
+
+#[test]
+fn chapter_numbers() {
+    assert_eq!(chapter_number("https://example.com/manga-ab123/chapter-45"), Some(45.0));
+    assert_eq!(chapter_number("https://example.com/read/ch_12.5/"), Some(12.5));
+    assert_eq!(chapter_number("https://example.com/s/one-piece/1093.html"), Some(1093.0));
+    assert_eq!(chapter_number("https://example.com/v2/episode/7?lang=en"), Some(7.0));
+    assert_eq!(chapter_number("Chapter 3: The Return"), Some(3.0));
+    assert_eq!(chapter_number("https://example.com/prologue"), None);
+}
+
+#[test]
+fn detects_direction() {
+    let newest_first = ["/c/chapter-3", "/c/chapter-2", "/c/chapter-1.5", "/c/chapter-1"];
+    assert_eq!(ListOrder::detect(&newest_first), ListOrder::Descending);
+    let ordered = reading_order(newest_first.to_vec(), ListOrder::Auto);
+    assert_eq!(ordered, ["/c/chapter-1", "/c/chapter-1.5", "/c/chapter-2", "/c/chapter-3"]);
+
+    // A stray extra doesn't flip a rising list
+    let oldest_first = ["/c/1", "/c/2", "/c/extra-99", "/c/3", "/c/4"];
+    assert_eq!(ListOrder::detect(&oldest_first), ListOrder::Ascending);
+    assert_eq!(ListOrder::detect(&["/c/prologue"]), ListOrder::Ascending);
+}
+
+#[test]
+fn override_wins() {
+    let listed = vec!["/c/1", "/c/2", "/c/3"];
+    assert_eq!(reading_order(listed.clone(), ListOrder::Descending), ["/c/3", "/c/2", "/c/1"]);
+    assert_eq!(reading_order(listed.clone(), ListOrder::Ascending), listed);
+}
+
+#[test]
+fn sequence_numbers() {
+    assert_eq!(
+        sequence(&["/c/chapter-1", "/c/chapter-1.5", "/c/chapter-12"]),
+        ["001", "001.5", "012"]
+    );
+    // Numbers that don't rise steadily fall back to positions
+    assert_eq!(sequence(&["/c/prologue", "/c/chapter-1"]), ["001", "002"]);
+    assert_eq!(sequence(&["/c/chapter-2", "/c/chapter-2"]), ["001", "002"]);
+}
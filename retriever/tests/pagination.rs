@@ -0,0 +1,80 @@
+mod common;
+
+use common::{retriever, serve, Request, Response};
+use reqwest::Url;
+use retriever::{chapters::same_chapter, page::Page, retriever::Retriever};
+use std::time::Duration;
+
+fn reader(image: &str, next_page: &str) -> String {
+    format!(
+        r#"<html><head><title>Chapter</title></head><body>
+<div class="pages"><img src="/img/{}.png"/></div>
+<a class="next-page" href="{}">Next page</a><a class="next" href="/ch/2">Next chapter</a>
+</body></html>"#,
+        image, next_page
+    )
+}
+
+fn route(req: &Request) -> Response {
+    Response::html(match req.path.as_str() {
+        "/ch/1" => reader("a1", "/ch/1/2"),
+        "/ch/1/2" => reader("a2", "/ch/1/3?from=2"),
+        "/ch/1/3?from=2" => reader("a3", "/ch/2"),
+        "/loop" => reader("b1", "/loop?page=2"),
+        "/loop?page=2" => reader("b2", "/loop"),
+        _ => reader("z", "#"),
+    })
+}
+
+async fn setup() -> (Retriever, String) {
+    let addr = serve(route).await;
+    let manifest = r#"
+name = "stub"
+hosts = ["127.0.0.1"]
+[selectors]
+links = { select = "a.next", attr = "href" }
+next = { select = "a.next", attr = "href" }
+next_page = { select = "a.next-page", attr = "href" }
+images = { select = "div.pages img", attr = "src" }
+"#;
+    let ret = retriever(manifest);
+    (ret, format!("http://{}", addr))
+}
+
+async fn images(ret: &Retriever, url: String) -> Vec<String> {
+    let mut chapter: Page = url.parse().unwrap();
+    ret.chapter_pages(&mut chapter, true, Duration::ZERO)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|p| p.url.path().to_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn follows_pages_to_chapter_end() {
+    let (ret, base) = setup().await;
+    let found = images(&ret, format!("{}/ch/1", base)).await;
+    assert_eq!(found, ["/img/a1.png", "/img/a2.png", "/img/a3.png"]);
+}
+
+#[tokio::test]
+async fn stops_on_page_cycle() {
+    let (ret, base) = setup().await;
+    let found = images(&ret, format!("{}/loop", base)).await;
+    assert_eq!(found, ["/img/b1.png", "/img/b2.png"]);
+}
+
+#[test]
+fn chapter_boundaries() {
+    let url = |s: &str| s.parse::<Url>().unwrap();
+    let chapter = url("https://example.com/read/ch-5");
+    assert!(same_chapter(&chapter, &url("https://example.com/read/ch-5?page=2")));
+    assert!(same_chapter(&chapter, &url("https://example.com/read/ch-5/2")));
+    assert!(same_chapter(&chapter, &url("https://example.com/read/ch-5-2.html")));
+    assert!(!same_chapter(&chapter, &url("https://example.com/read/ch-50")));
+    assert!(!same_chapter(&chapter, &url("https://example.com/read/ch-6")));
+    assert!(!same_chapter(&chapter, &url("https://mirror.example.com/read/ch-5/2")));
+    let html = url("https://example.com/read/ch-5.html");
+    assert!(same_chapter(&html, &url("https://example.com/read/ch-5_2.html")));
+}
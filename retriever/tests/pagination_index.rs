@@ -0,0 +1,77 @@
+mod common;
+
+use common::{retriever, serve, Request, Response};
+use retriever::page::{ContentType, Page};
+
+fn index(chapters: &[u32], next: Option<&str>) -> String {
+    let items = chapters
+        .iter()
+        .map(|c| format!(r#"<li><a href="/ch/{}">Chapter {}</a></li>"#, c, c))
+        .collect::<String>();
+    let next = next
+        .map(|n| format!(r#"<a class="older" href="{}">Older</a>"#, n))
+        .unwrap_or_default();
+    format!(
+        r#"<html><head><title>Series</title></head><body>
+<ul class="chapters">{}</ul>{}</body></html>"#,
+        items, next
+    )
+}
+
+fn route(req: &Request) -> Response {
+    Response::html(match req.path.as_str() {
+        "/linked" => index(&[1, 2], Some("/linked/p/2")),
+        "/linked/p/2" => index(&[3, 4], Some("/linked/p/3#list")),
+        "/linked/p/3" => index(&[5, 1], None),
+        "/counted" => index(&[1, 2], None),
+        "/counted?page=2" => index(&[2, 3], None),
+        "/counted?page=3" => index(&[4], None),
+        // Past the end the site repeats its last page
+        _ => index(&[4], None),
+    })
+}
+
+async fn chapters(pagination: &str, path: &str) -> Vec<String> {
+    let addr = serve(route).await;
+    let manifest = format!(
+        r#"
+name = "stub"
+hosts = ["127.0.0.1"]
+[selectors]
+links = {{ select = "ul.chapters a", attr = "href" }}
+text = {{ select = "li" }}
+[pagination]
+{}
+"#,
+        pagination
+    );
+    let ret = retriever(&manifest);
+
+    let mut page: Page = format!("http://{}{}", addr, path).parse().unwrap();
+    let page = ret.fetch_links(&mut page, false).await.unwrap();
+    match &page.content.data {
+        Some(ContentType::Chapters(links)) => links
+            .iter()
+            .map(|l| l.rsplit('/').next().unwrap().to_owned())
+            .collect(),
+        other => panic!("no chapters: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn next_link() {
+    let found = chapters(r#"next = { select = "a.older" }"#, "/linked").await;
+    assert_eq!(found, ["1", "2", "3", "4", "5"]);
+}
+
+#[tokio::test]
+async fn url_template() {
+    let found = chapters(r#"template = "?page={page}""#, "/counted").await;
+    assert_eq!(found, ["1", "2", "3", "4"]);
+}
+
+#[tokio::test]
+async fn page_limit() {
+    let found = chapters("template = \"?page={page}\"\nmax = 2", "/counted").await;
+    assert_eq!(found, ["1", "2", "3"]);
+}
@@ -0,0 +1,49 @@
+use retriever::{
+    error::Error,
+    extractor::Extractor,
+    page::Page,
+    resume::{remove_partials, Resume},
+    retriever::Retriever,
+};
+use std::fs;
+
+#[tokio::test]
+async fn state_round_trip() {
+    let dir = std::env::temp_dir().join(format!("retriever-resume-{}", std::process::id()));
+    let chapter = dir.join("chapter-1");
+    fs::create_dir_all(&chapter).unwrap();
+    fs::write(chapter.join("01.png.part"), b"half").unwrap();
+    fs::write(chapter.join("00.png"), b"whole").unwrap();
+    // Not written by us, or not a file
+    fs::write(dir.join("movie.mkv.part"), b"someone else's").unwrap();
+    std::os::unix::fs::symlink(&dir, chapter.join("02.png.part")).unwrap();
+    let leaves = ["00.png", "01.png", "02.png"].map(|f| chapter.join(f));
+    assert_eq!(remove_partials(leaves.iter().map(|p| p.as_path())).unwrap(), 1);
+    assert!(!chapter.join("01.png.part").exists());
+    assert!(chapter.join("00.png").exists());
+    assert!(dir.join("movie.mkv.part").exists());
+    assert!(fs::symlink_metadata(chapter.join("02.png.part")).is_ok());
+
+    let mut state = Resume::new("https://example.com/series");
+    state.done.insert("https://example.com/img/0.png".to_owned());
+    state.pending.push("https://example.com/img/1.png".to_owned());
+    state.save(&dir).await.unwrap();
+    let loaded = Resume::load(&dir).await.unwrap();
+    assert_eq!(loaded, state);
+    assert!(loaded.interrupted_at > 0);
+    Resume::clear(&dir).await.unwrap();
+    assert!(Resume::load(&dir).await.is_err());
+    Resume::clear(&dir).await.unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn cancelled_retriever_sends_nothing() {
+    let ret = Retriever::default();
+    ret.cancellation().cancel();
+    let mut page: Page = "http://127.0.0.1:9/never".parse().unwrap();
+    let res = ret.fetch(&mut page, &Extractor::new(), false).await;
+    assert!(matches!(res, Err(Error::Cancelled)));
+    assert!(page.last.is_none());
+    assert!(ret.stats().is_empty());
+}
@@ -0,0 +1,44 @@
+use retriever::robots::Robots;
+use std::time::Duration;
+
+#[test]
+fn robots_rules() {
+    let src = "User-agent: *\n\
+               Disallow: /private\n\
+               Allow: /private/open\n\
+               Disallow: /*.php$\n\
+               Crawl-delay: 2\n\
+               \n\
+               User-agent: retriever\n\
+               Disallow: /manga/\n";
+    let robots = Robots::parse(src, "retriever");
+    assert!(!robots.allowed("/manga/chapter-1"));
+    assert!(robots.allowed("/private"));
+    assert_eq!(robots.crawl_delay(), None);
+
+    let robots = Robots::parse(src, "other");
+    assert!(!robots.allowed("/private/closed"));
+    assert!(robots.allowed("/private/open/1"));
+    assert!(!robots.allowed("/index.php"));
+    assert!(robots.allowed("/index.php?x=1"));
+    assert_eq!(robots.crawl_delay(), Some(Duration::from_secs(2)));
+}
+
+#[test]
+fn agent_tokens() {
+    let src = "User-agent: *\n\
+               Disallow: /all\n\
+               \n\
+               User-agent: r\n\
+               User-agent: retrieverbot\n\
+               Disallow: /others\n";
+    // Groups only sharing part of the name don't apply
+    let robots = Robots::parse(src, "retriever");
+    assert!(!robots.allowed("/all"));
+    assert!(robots.allowed("/others"));
+
+    let src = format!("{}\nUser-agent: ReTriever/2.1\nDisallow: /ours\n", src);
+    let robots = Robots::parse(&src, "retriever");
+    assert!(robots.allowed("/all"));
+    assert!(!robots.allowed("/ours"));
+}
@@ -0,0 +1,43 @@
+use retriever::{
+    extractor::{Field, Manifest},
+    page::{ContentType, Page},
+};
+
+#[tokio::test]
+async fn scripted_fields() {
+    let manifest: Manifest = r#"
+name = "scripted"
+hosts = ["example.com"]
+[scripts]
+title = 'select(html, "h1.title")[0]'
+images = '''
+let start = html.index_of("var pages = [") + 13;
+let list = html.sub_string(start);
+list.sub_string(0, list.index_of("]")).split(",").map(|s| {
+    s.trim();
+    s.replace("\"", "");
+    join_url(url, s)
+})
+'''
+"#
+    .parse()
+    .unwrap();
+    assert!(manifest.scripts().contains_key(&Field::Images));
+    let extractor = manifest.extractor().unwrap();
+    let mut page: Page = "https://example.com/read/1".parse().unwrap();
+    page.html = Some(
+        r#"<html><body><h1 class="title">Chapter 1</h1>
+<script>var pages = ["/img/1.png", "/img/2.png"];</script></body></html>"#
+            .to_owned(),
+    );
+    assert_eq!(extractor.get_title(&page).await.as_deref(), Some("Chapter 1"));
+    match extractor.get_images(&page).await {
+        Some(ContentType::Images(images, _)) => assert_eq!(
+            images,
+            ["https://example.com/img/1.png", "https://example.com/img/2.png"]
+        ),
+        other => panic!("unexpected images: {:?}", other),
+    }
+    let broken: Result<Manifest, _> = "[scripts]\ntitle = 'let = ;'".parse();
+    assert!(broken.unwrap().extractor().is_err());
+}
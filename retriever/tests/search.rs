@@ -0,0 +1,43 @@
+use retriever::{search::Search, selector::Selector};
+use select::document::Document;
+
+const RESULTS: &str = r#"<html><body><div id="results">
+<div class="item"><a href="/series/one" title="One Piece"><img data-src="/covers/1.jpg"/></a></div>
+<div class="item featured"><a href="https://example.com/series/two">Two</a></div>
+<p class="item"><a href="/nope">not a result</a></p>
+</div></body></html>"#;
+
+#[test]
+fn selectors() {
+    let doc = Document::from(RESULTS);
+    let count = |s: &str| s.parse::<Selector>().unwrap().find(&doc).len();
+    assert_eq!(count("div.item"), 2);
+    assert_eq!(count("#results > div.featured a"), 1);
+    assert_eq!(count("a[href^=\"/series\"]"), 1);
+    assert_eq!(count("div.item a, p.item a"), 3);
+    assert_eq!(count("body > a"), 0);
+    assert_eq!(count("a[title=\"One Piece, Vol. 1\"], a[title='One Piece']"), 1);
+    assert_eq!(count("[title=\"a,b\"]"), 0);
+    assert!("div:first-child".parse::<Selector>().is_err());
+}
+
+#[test]
+fn search_results() {
+    let search = Search {
+        url: "https://example.com/search?q={query}".to_owned(),
+        results: "div.item".to_owned(),
+        ..Default::default()
+    };
+    assert_eq!(
+        search.url_for("one piece").unwrap().as_str(),
+        "https://example.com/search?q=one+piece"
+    );
+    let base = "https://example.com/search".parse().unwrap();
+    let results = search.results(RESULTS, &base).unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].title, "One Piece");
+    assert_eq!(results[0].url, "https://example.com/series/one");
+    assert_eq!(results[0].cover.as_deref(), Some("https://example.com/covers/1.jpg"));
+    assert_eq!(results[1].title, "Two");
+    assert_eq!(results[1].cover, None);
+}
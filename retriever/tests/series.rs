@@ -0,0 +1,116 @@
+mod common;
+
+use common::{retriever, serve, temp_dir, Request, Response, IMAGE_SITE, NOT_FOUND};
+use futures::StreamExt;
+use retriever::{
+    download::{DownloadEvent, Options},
+    extractor::Manifest,
+    page::Page,
+    presets::default_series,
+    series::{SeriesInfo, Status},
+};
+use std::fs;
+
+const INDEX: &str = r#"<html lang="en"><head><title>Some Manga - Read Online</title>
+<meta property="og:title" content="Some Manga">
+<meta property="og:image" content="/covers/some-manga.jpg">
+</head><body>
+<table>
+<tr><td>Author(s) :</td><td><a href="/a/1">Ann Author</a>, <a href="/a/2">Bo Writer</a></td></tr>
+<tr><td>Artist:</td><td><a href="/a/3">Cy Artist</a></td></tr>
+</table>
+<ul>
+<li><b>Genres:</b> Action, Drama; Comedy</li>
+<li><span>Status:</span> <span>Completed</span></li>
+<li>Alternative: Aru Manga | Ein Manga</li>
+</ul>
+<div class="summary-content"><p>A long story about things.</p></div>
+<a class="home" href="/series">Series</a>
+<ul class="chapters"><li><a href="/ch/1">One</a></li></ul>
+</body></html>"#;
+const CHAPTER: &str = r#"<html><head><title>One</title></head><body>
+<div class="pages"><img src="/img/1.png"/></div></body></html>"#;
+
+fn route(req: &Request) -> Response {
+    match req.path.as_str() {
+        "/series" => Response::html(INDEX),
+        "/ch/1" => Response::html(CHAPTER),
+        p if p.starts_with("/img/") => Response::image("not really a png"),
+        _ => Response::html(NOT_FOUND),
+    }
+}
+
+#[test]
+fn labelled_metadata() {
+    let mut page: Page = "https://example.com/manga/some".parse().unwrap();
+    page.html = Some(INDEX.to_owned());
+    let series = default_series(&page).unwrap();
+    assert_eq!(series.title.as_deref(), Some("Some Manga"));
+    assert_eq!(series.url.as_deref(), Some("https://example.com/manga/some"));
+    assert_eq!(series.authors, ["Ann Author", "Bo Writer"]);
+    assert_eq!(series.artists, ["Cy Artist"]);
+    assert_eq!(series.genres, ["Action", "Drama", "Comedy"]);
+    assert_eq!(series.alt_titles, ["Aru Manga", "Ein Manga"]);
+    assert_eq!(series.status, Status::Completed);
+    assert_eq!(series.description.as_deref(), Some("A long story about things."));
+    assert_eq!(series.cover.as_deref(), Some("https://example.com/covers/some-manga.jpg"));
+    assert_eq!(series.language.as_deref(), Some("en"));
+}
+
+#[test]
+fn sparse_page() {
+    let mut page: Page = "https://example.com/novel".parse().unwrap();
+    page.html = Some("<html><head><title>Novel</title></head><body></body></html>".to_owned());
+    let series = default_series(&page).unwrap();
+    assert_eq!(series.title.as_deref(), Some("Novel"));
+    assert!(series.authors.is_empty() && series.genres.is_empty());
+    assert_eq!(series.status, Status::Unknown);
+    assert_eq!((series.cover, series.language), (None, None));
+}
+
+#[tokio::test]
+async fn site_rules() {
+    let manifest: Manifest = r#"
+name = "stub"
+[selectors]
+artists = { select = "td a" }
+genres = { select = "ul.none li" }
+description = { select = "title" }
+cover = { select = "a.home", attr = "href" }
+"#
+    .parse()
+    .unwrap();
+    let extractor = manifest.extractor().unwrap();
+    let mut page: Page = "https://example.com/manga/some".parse().unwrap();
+    page.html = Some(INDEX.to_owned());
+    let series = extractor.get_series(&page).await.unwrap();
+    assert_eq!(series.artists, ["Ann Author", "Bo Writer", "Cy Artist"]);
+    assert!(series.genres.is_empty());
+    assert_eq!(series.description.as_deref(), Some("Some Manga - Read Online"));
+    assert_eq!(series.cover.as_deref(), Some("https://example.com/series"));
+    // Attributes without a rule still come from the preset
+    assert_eq!(series.authors, ["Ann Author", "Bo Writer"]);
+    assert_eq!(series.status, Status::Completed);
+}
+
+#[tokio::test]
+async fn written_by_downloads() {
+    let addr = serve(route).await;
+    let ret = retriever(IMAGE_SITE);
+    let dir = temp_dir("series");
+    let mut options = Options::default();
+    options.images(true).output_dir(&dir);
+
+    let url = format!("http://{}/series", addr).parse().unwrap();
+    let events = ret.download_series(url, options).collect::<Vec<_>>().await;
+    let emitted = events.iter().find_map(|e| match e {
+        DownloadEvent::Series(s) => Some(s.clone()),
+        _ => None,
+    });
+    let saved = SeriesInfo::load(&dir).await.unwrap();
+    assert_eq!(emitted, Some(saved.clone()));
+    assert_eq!(saved.title.as_deref(), Some("Some Manga"));
+    assert_eq!(saved.authors, ["Ann Author", "Bo Writer"]);
+    assert_eq!(saved.cover, Some(format!("http://{}/covers/some-manga.jpg", addr)));
+    fs::remove_dir_all(&dir).unwrap();
+}
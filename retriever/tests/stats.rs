@@ -0,0 +1,60 @@
+mod common;
+
+use common::{serve, Response};
+use retriever::{
+    extractor::Extractor,
+    page::Page,
+    retriever::Retriever,
+    stats::{table, Stats},
+};
+use std::time::Duration;
+
+#[tokio::test]
+async fn per_host_counters() {
+    let addr = serve(|req| match req.path.as_str() {
+        "/ok" => Response::html("<html><body>fine</body></html>"),
+        _ => Response::html("gone").status("404 Not Found"),
+    })
+    .await;
+    let ret = Retriever::default();
+    let extractor = Extractor::new();
+    let mut ok: Page = format!("http://{}/ok", addr).parse().unwrap();
+    let mut missing: Page = format!("http://{}/missing", addr).parse().unwrap();
+    ret.fetch(&mut ok, &extractor, false).await.unwrap();
+    let mut again: Page = format!("http://{}/ok", addr).parse().unwrap();
+    ret.fetch(&mut again, &extractor, false).await.unwrap();
+    ret.fetch(&mut missing, &extractor, false).await.unwrap();
+    ret.check_page(&mut ok, false).await.unwrap();
+
+    let stats = ret.stats();
+    assert_eq!(stats.len(), 1);
+    let host = &stats[0];
+    assert_eq!(host.host, "127.0.0.1");
+    assert_eq!(host.requests, 3);
+    assert_eq!(host.bytes, 30 * 2 + 4);
+    assert_eq!(host.statuses.get(&200), Some(&2));
+    assert_eq!(host.statuses.get(&404), Some(&1));
+    assert_eq!((host.class(2), host.class(4)), (2, 1));
+    assert_eq!(host.cache_hits, 1);
+    assert!(host.p50_ms <= host.p90_ms && host.p90_ms <= host.p99_ms);
+    assert!(table(&stats).lines().nth(1).unwrap().starts_with("127.0.0.1"));
+}
+
+#[test]
+fn latency_percentiles() {
+    let stats = Stats::default();
+    for ms in 1..=1000 {
+        stats.response("example.com", 200, 0, Duration::from_millis(ms));
+    }
+    stats.cache_hit("example.org");
+    let snapshot = stats.snapshot();
+    let host = &snapshot[0];
+    // Within the width of a bucket
+    for (got, want) in [(host.p50_ms, 500), (host.p90_ms, 900), (host.p99_ms, 990)] {
+        assert!(got >= want && got <= want * 6 / 5, "{} for {}", got, want);
+    }
+    assert_eq!(stats.snapshot()[1].p99_ms, 0);
+    // One slow outlier doesn't move the tail
+    stats.response("example.com", 200, 0, Duration::from_secs(600));
+    assert!(stats.snapshot()[0].p99_ms <= 990 * 6 / 5);
+}
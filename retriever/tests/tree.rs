@@ -0,0 +1,63 @@
+use retriever::page::{volume_of, ContentType, Node};
+use std::{fs, path::PathBuf};
+
+fn chapter(name: &str, images: &[&str]) -> ContentType {
+    let mut node = Node::new(name);
+    for i in images {
+        let image = ContentType::Image(i.as_bytes().to_vec());
+        node.push(format!("https://example.com/img/{}", i), image);
+    }
+    ContentType::Chapter(node)
+}
+
+#[test]
+fn volumes() {
+    assert_eq!(volume_of("Vol. 2 Ch. 10"), Some(2));
+    assert_eq!(volume_of("https://example.com/series/volume-12/chapter-3"), Some(12));
+    assert_eq!(volume_of("Revolution 9"), None);
+    assert_eq!(volume_of("Chapter 4"), None);
+}
+
+#[tokio::test]
+async fn series_tree() {
+    let tree = ContentType::series("Some Series", vec![
+        ("https://example.com/prologue".to_owned(), chapter("001 Prologue", &["p.jpg"])),
+        ("https://example.com/v1/1".to_owned(), chapter("002 Vol.1 Ch.1", &["a.png", "b.png"])),
+        ("https://example.com/v1/2".to_owned(), chapter("003 Vol.1 Ch.2", &["c"])),
+        ("https://example.com/v2/3".to_owned(), chapter("004 Vol.2 Ch.3", &["d.webp"])),
+    ]);
+    let root = tree.node().unwrap();
+    assert_eq!(root.children.len(), 3);
+    assert!(matches!(
+        &root.children[1],
+        ContentType::Volume(v) if v.name == "Volume 1" && v.children.len() == 2
+    ));
+    assert_eq!(root.sources[1], "");
+
+    let leaves = tree.leaves();
+    let paths = leaves.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>();
+    assert_eq!(paths, [
+        PathBuf::from("001 Prologue/001.jpg"),
+        PathBuf::from("Volume 1/002 Vol.1 Ch.1/001.png"),
+        PathBuf::from("Volume 1/002 Vol.1 Ch.1/002.png"),
+        PathBuf::from("Volume 1/003 Vol.1 Ch.2/001.bin"),
+        PathBuf::from("Volume 2/004 Vol.2 Ch.3/001.webp"),
+    ]);
+    assert_eq!(leaves[2].1, "https://example.com/img/b.png");
+
+    let dir = std::env::temp_dir().join(format!("retriever-tree-{}", std::process::id()));
+    tree.save(&dir).await.unwrap();
+    for (path, _) in &leaves {
+        assert!(dir.join(path).is_file(), "{:?}", path);
+    }
+    assert_eq!(fs::read(dir.join("Volume 1/002 Vol.1 Ch.1/002.png")).unwrap(), b"b.png");
+    assert_eq!(
+        fs::read_to_string(dir.join("Volume 1/sources.lst")).unwrap(),
+        "https://example.com/v1/1\nhttps://example.com/v1/2\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("sources.lst")).unwrap(),
+        "https://example.com/prologue\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
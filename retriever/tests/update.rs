@@ -0,0 +1,123 @@
+mod common;
+
+use common::{retriever, serve, temp_dir, Request, Response, IMAGE_SITE, NOT_FOUND};
+use futures::StreamExt;
+use retriever::{
+    download::{DownloadEvent, Options},
+    series::SeriesManifest,
+};
+use std::{
+    fs,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Set once the site publishes its third chapter
+static RELEASED: AtomicBool = AtomicBool::new(false);
+
+fn series() -> String {
+    let mut chapters = vec![(1, "One"), (2, "Two")];
+    if RELEASED.load(Ordering::SeqCst) {
+        chapters.push((3, "Three"));
+    }
+    let items = chapters
+        .iter()
+        .map(|(n, name)| format!(r#"<li><a href="/ch/{}">{}</a></li>"#, n, name))
+        .collect::<String>();
+    format!(
+        r#"<html><head><title>Series</title></head><body>
+<a class="home" href="/series">Series</a>
+<ul class="chapters">{}</ul></body></html>"#,
+        items
+    )
+}
+
+fn chapter(name: &str, images: &[&str]) -> String {
+    let images = images
+        .iter()
+        .map(|i| format!(r#"<img src="/img/{}.png"/>"#, i))
+        .collect::<String>();
+    format!(
+        r#"<html><head><title>{}</title></head><body>
+<div class="pages">{}</div></body></html>"#,
+        name, images
+    )
+}
+
+fn route(req: &Request) -> Response {
+    match req.path.as_str() {
+        "/series" => Response::html(series()),
+        "/ch/1" => Response::html(chapter("One", &["a1", "a2"])),
+        "/ch/2" => Response::html(chapter("Two", &["b1"])),
+        // Ends on a page the second chapter has too
+        "/ch/3" => Response::html(chapter("Three", &["c1", "b1"])),
+        p if p.starts_with("/img/") => Response::image("not really a png"),
+        _ => Response::html(NOT_FOUND),
+    }
+}
+
+#[tokio::test]
+async fn downloads_only_new_chapters() {
+    let addr = serve(route).await;
+    let ret = retriever(IMAGE_SITE);
+    let dir = temp_dir("update");
+    let mut options = Options::default();
+    options.images(true).output_dir(&dir);
+
+    let url = format!("http://{}/series", addr).parse().unwrap();
+    let events = ret.download_series(url, options).collect::<Vec<_>>().await;
+    assert!(matches!(
+        events.last(),
+        Some(DownloadEvent::Finished { saved: 3, failed: 0 })
+    ));
+    let saved = SeriesManifest::load(&dir).await.unwrap();
+    assert!(saved.images);
+    assert_eq!(saved.chapters.len(), 2);
+    assert_eq!(saved.chapters[0].name, "001 One");
+    assert_eq!(saved.chapters[0].pages.len(), 2);
+
+    RELEASED.store(true, Ordering::SeqCst);
+    let mut options = Options::default();
+    options.output_dir(&dir);
+    let events = ret.update_series(options).await.unwrap().collect::<Vec<_>>().await;
+    let added = events.iter().find_map(|e| match e {
+        DownloadEvent::Diff {
+            added,
+            changed,
+            removed,
+        } => {
+            assert!(changed.is_empty() && removed.is_empty());
+            Some(added.iter().map(|u| u.path().to_owned()).collect::<Vec<_>>())
+        }
+        _ => None,
+    });
+    assert_eq!(added.unwrap(), ["/ch/3"]);
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, DownloadEvent::Queued { total: 2, skipped: 0 })));
+    assert!(matches!(
+        events.last(),
+        Some(DownloadEvent::Finished { saved: 2, failed: 0 })
+    ));
+    assert!(dir.join("003 Three").join("002.png").exists());
+    let chapters = fs::read_to_string(dir.join("sources.lst")).unwrap();
+    assert_eq!(chapters.lines().count(), 3);
+    let saved = SeriesManifest::load(&dir).await.unwrap();
+    assert_eq!(saved.chapters.len(), 3);
+    assert_eq!(saved.chapters[2].pages, [
+        format!("http://{}/img/c1.png", addr),
+        format!("http://{}/img/b1.png", addr),
+    ]);
+
+    // Nothing new, nothing fetched
+    let mut options = Options::default();
+    options.output_dir(&dir);
+    let events = ret.update_series(options).await.unwrap().collect::<Vec<_>>().await;
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, DownloadEvent::Diff { added, .. } if added.is_empty())));
+    assert!(matches!(
+        events.last(),
+        Some(DownloadEvent::Finished { saved: 0, failed: 0 })
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+}
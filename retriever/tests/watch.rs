@@ -0,0 +1,138 @@
+mod common;
+
+use common::{retriever, serve, temp_dir, Request, Response, IMAGE_SITE, NOT_FOUND};
+use futures::StreamExt;
+use retriever::{
+    series::SeriesManifest,
+    watch::{parse_interval, WatchConfig, WatchEvent, WatchState},
+};
+use std::{fs, time::Duration};
+use tokio::time::timeout;
+
+fn series(name: &str) -> String {
+    format!(
+        r#"<html><head><title>{0}</title></head><body>
+<a class="home" href="/{0}">{0}</a>
+<ul class="chapters"><li><a href="/{0}/ch/1">One</a></li></ul></body></html>"#,
+        name
+    )
+}
+
+const CHAPTER: &str = r#"<html><head><title>One</title></head><body>
+<div class="pages"><img src="/img/1.png"/></div></body></html>"#;
+
+fn route(req: &Request) -> Response {
+    match req.path.as_str() {
+        p @ ("/alpha" | "/beta") => Response::html(series(&p[1..])),
+        p if p.contains("/ch/") => Response::html(CHAPTER),
+        p if p.starts_with("/img/") => Response::image("not really a png"),
+        _ => Response::html(NOT_FOUND),
+    }
+}
+
+#[test]
+fn intervals() {
+    assert_eq!(parse_interval("90"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_interval("30m"), Ok(Duration::from_secs(30 * 60)));
+    assert_eq!(parse_interval("1.5h"), Ok(Duration::from_secs(90 * 60)));
+    assert_eq!(parse_interval("400ms"), Ok(Duration::from_millis(400)));
+    assert!(parse_interval("2w").is_err());
+    assert!(parse_interval("-1s").is_err());
+}
+
+#[test]
+fn schedule() {
+    let config: WatchConfig = r#"
+library = "/srv/library"
+interval = "1h"
+host_delay = "30s"
+[[series]]
+url = "https://example.com/series/some-manga/"
+[[series]]
+url = "https://example.com/novel/7"
+name = "Some: Novel"
+interval = "1d"
+"#
+    .parse()
+    .unwrap();
+    let (manga, novel) = (&config.series[0], &config.series[1]);
+    assert_eq!(config.dir_of(manga), config.library.join("some-manga"));
+    assert_eq!(config.dir_of(novel), config.library.join("Some_ Novel"));
+    assert!("interval = \"soon\"".parse::<WatchConfig>().is_err());
+
+    let mut state = WatchState::default();
+    assert_eq!(state.ready_at(manga, config.host_delay), 0);
+    let next = state.checked(manga, config.interval_of(manga), false, 1000);
+    assert_eq!(next, 1000 + 3600);
+    // Same host, so the novel waits for it to rest
+    assert_eq!(state.ready_at(novel, config.host_delay), 1030);
+
+    // Failures retry sooner, backing off up to the interval
+    assert_eq!(state.checked(novel, config.interval_of(novel), true, 2000), 2000 + 300);
+    assert_eq!(state.checked(novel, config.interval_of(novel), true, 3000), 3000 + 600);
+    assert_eq!(state.checked(manga, config.interval_of(manga), true, 4000), 4000 + 300);
+    assert_eq!(state.checked(manga, config.interval_of(manga), true, 5000), 5000 + 600);
+    assert_eq!(state.checked(manga, config.interval_of(manga), true, 6000), 6000 + 1200);
+    assert_eq!(state.checked(manga, config.interval_of(manga), true, 7000), 7000 + 2400);
+    assert_eq!(state.checked(manga, config.interval_of(manga), true, 8000), 8000 + 3600);
+    assert_eq!(state.series[manga.url.as_str()].failures, 5);
+    state.checked(manga, config.interval_of(manga), false, 9000);
+    assert_eq!(state.series[manga.url.as_str()].failures, 0);
+}
+
+#[tokio::test]
+async fn downloads_and_keeps_schedule() {
+    let addr = serve(route).await;
+    let library = temp_dir("watch");
+    let config: WatchConfig = format!(
+        r#"
+library = {:?}
+interval = "1h"
+host_delay = "0s"
+delay = "0s"
+[[series]]
+url = "http://{1}/alpha"
+images = true
+[[series]]
+url = "http://{1}/beta"
+images = true
+"#,
+        library, addr
+    )
+    .parse()
+    .unwrap();
+
+    let ret = retriever(IMAGE_SITE);
+    let events = ret.watch(config.clone());
+    futures::pin_mut!(events);
+    let mut checked = vec![];
+    while let Some(event) = events.next().await {
+        if let WatchEvent::Checked { url, added, failed, .. } = event {
+            assert!(!failed);
+            assert_eq!(added.len(), 1);
+            checked.push(url.path().to_owned());
+            if checked.len() == 2 {
+                ret.cancellation().cancel();
+            }
+        }
+    }
+    assert_eq!(checked, ["/alpha", "/beta"]);
+    for name in ["alpha", "beta"] {
+        let dir = library.join(name);
+        assert_eq!(SeriesManifest::load(&dir).await.unwrap().chapters.len(), 1);
+        assert!(dir.join("001 One").join("001.png").exists());
+    }
+    let state = WatchState::load(&library).await.unwrap();
+    assert_eq!(state.series.len(), 2);
+    assert!(state.series.values().all(|s| s.next_check - s.last_check == 3600));
+
+    // A restarted watch has nothing due for an hour
+    let ret = retriever(IMAGE_SITE);
+    let events = ret.watch(config);
+    futures::pin_mut!(events);
+    let first = timeout(Duration::from_secs(5), events.next()).await.unwrap();
+    assert!(matches!(first, Some(WatchEvent::Waiting { .. })));
+    ret.cancellation().cancel();
+    assert!(events.next().await.is_none());
+    fs::remove_dir_all(&library).unwrap();
+}